@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use libra_types::account_address::AccountAddress;
+
+/// Where `{{name}}` placeholders were replaced in a source, to map spans of the
+/// substituted text back to the original one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Substitution {
+    edits: Vec<Edit>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Edit {
+    /// Offset in the substituted text.
+    offset: usize,
+    original_len: usize,
+    len: usize,
+}
+
+impl Substitution {
+    /// Span in the original source of a span in the substituted text.
+    pub fn original(&self, span: Range<usize>) -> Range<usize> {
+        self.original_offset(span.start)..self.original_offset(span.end)
+    }
+
+    fn original_offset(&self, offset: usize) -> usize {
+        let mut delta: isize = 0;
+        for edit in &self.edits {
+            if offset < edit.offset {
+                break;
+            }
+            if offset < edit.offset + edit.len {
+                // Inside a replaced address, point at its placeholder.
+                return (edit.offset as isize - delta) as usize;
+            }
+            delta += edit.len as isize - edit.original_len as isize;
+        }
+        (offset as isize - delta) as usize
+    }
+}
+
+/// Replace the `{{name}}` placeholders of a Move IR source with the named addresses.
+///
+/// Returns the span and name of the first unknown placeholder on failure.
+pub fn substitute(
+    source: &str,
+    addresses: &BTreeMap<String, AccountAddress>,
+) -> Result<(String, Substitution), (Range<usize>, String)> {
+    let mut text = String::with_capacity(source.len());
+    let mut substitution = Substitution::default();
+    let mut rest = source;
+    let mut consumed = 0;
+
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end + 2,
+            None => break,
+        };
+        let name = rest[start + 2..end - 2].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            text.push_str(&rest[..start + 2]);
+            consumed += start + 2;
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let address = match addresses.get(name) {
+            Some(address) => format!("0x{:x}", address),
+            None => return Err((consumed + start..consumed + end, name.to_string())),
+        };
+        text.push_str(&rest[..start]);
+        substitution.edits.push(Edit {
+            offset: text.len(),
+            original_len: end - start,
+            len: address.len(),
+        });
+        text.push_str(&address);
+        consumed += end;
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    Ok((text, substitution))
+}
+
+#[test]
+fn test_substitute() {
+    let mut addresses = BTreeMap::new();
+    addresses.insert("sender".to_string(), AccountAddress::new([0x12; 16]));
+    let source = "import {{sender}}.M;\nmain() { M.f(); }";
+    let (text, substitution) = substitute(source, &addresses).unwrap();
+    assert_eq!(
+        text,
+        "import 0x12121212121212121212121212121212.M;\nmain() { M.f(); }"
+    );
+
+    let main = text.find("main").unwrap();
+    assert_eq!(
+        substitution.original(main..main + 4),
+        source.find("main").unwrap()..source.find("main").unwrap() + 4
+    );
+    assert_eq!(substitution.original(10..12), 7..7);
+
+    let err = substitute("import {{bob}}.M;", &addresses).unwrap_err();
+    assert_eq!(err, (7..14, "bob".to_string()));
+}
@@ -0,0 +1,59 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use libra_crypto::HashValue;
+
+/// Compiled artifacts stored in the target directory, keyed by the hash of their inputs.
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Hash the inputs of a compilation into a cache key.
+    pub fn key(parts: &[&[u8]]) -> String {
+        let mut buf = vec![];
+        for part in parts {
+            // Prefix every part with its length so that parts can not run into each other.
+            buf.extend_from_slice(&(part.len() as u64).to_le_bytes());
+            buf.extend_from_slice(part);
+        }
+        HashValue::from_sha3_256(&buf).to_hex()
+    }
+
+    pub fn get(&self, key: &str, extension: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(key, extension)).ok()
+    }
+
+    /// Store an artifact, the cache is an optimization so failures are ignored.
+    ///
+    /// Artifacts are written to a temporary file first and renamed into place, so that
+    /// concurrent builds never read a partially written entry.
+    pub fn put(&self, key: &str, extension: &str, bytes: &[u8]) {
+        static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        let tmp = self.dir.join(format!(
+            "{}.{}.{}.{}.tmp",
+            key,
+            extension,
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if fs::write(&tmp, bytes).is_err() || fs::rename(&tmp, self.path(key, extension)).is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    fn path(&self, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(key).with_extension(extension)
+    }
+}
@@ -1,55 +1,133 @@
-use bytecode_verifier::verifier::VerifiedModule;
+use std::{fs, path::PathBuf};
 
-use glob::glob;
+use rayon::prelude::*;
 
 use crate::{commands::Command, config::Config, Parameter, println_color, runner::MoveRunner};
+use crate::commands::{
+    compile_modules, dependency_dirs, exit_with_errors, lock, source_files, unwrap_or_exit,
+};
+use crate::commands::watch::{affected_scripts, changed_in, touches, watch};
+use crate::diagnostics::{Diagnostic, emit};
+use crate::manifest::MANIFEST_FILE;
 
 pub struct BuildCommand {}
 
 impl Command for BuildCommand {
     fn execute(&self, params: Parameter) {
-        if let Parameter::Build { home } = params {
-            let cfg = Config::load_config(home);
-            let mut runner = MoveRunner::new(cfg.clone());
-
-            println_color("Loading");
-            print!("modules from {}\n", cfg.script_dir().display());
-            let mdir = glob(&format!("{}/**/*.mvir", &cfg.module_dir().display()))
-                .expect("Module directory is not valid.");
-
-            for entry in mdir {
-                match entry {
-                    Ok(path) => {
-                        println_color("Compiling");
-                        print!("{:?}\n", &path.display());
-                        let m: VerifiedModule = runner.complie_module(&path);
-                        let cm = &m.as_inner();
-                        runner.datastore.add_module(&cm.self_id(), cm);
-                    }
-                    Err(_) => {
-                        panic!("Failed to load modules source file.");
-                    }
-                }
+        if let Parameter::Build {
+            home,
+            watch: watching,
+            locked,
+            ..
+        } = params
+        {
+            let (cfg, packages) = Config::load_workspace(home);
+            let mut runner = unwrap_or_exit(MoveRunner::new(cfg.clone()));
+
+            let mut errors = compile_modules(&packages, &mut runner);
+            for package in &packages {
+                println_color("Loading");
+                print_status!("scripts from {}\n", package.script_dir().display());
+                let scripts = source_files(&package.script_dir());
+                errors.extend(compile_scripts(&mut runner, package, scripts));
             }
 
-            println_color("Loading");
-            print!("scripts from {}\n", cfg.script_dir().display());
-            let sdir = glob(&format!("{}/**/*.mvir", cfg.script_dir().display()))
-                .expect("Script Directory is not valid");
-
-            for entry in sdir {
-                match entry {
-                    Ok(path) => {
-                        println_color("Compiling");
-                        print!("{:?}\n", &path.display());
-                        &runner.complie_script(&path);
-                    }
-                    Err(_) => {
-                        panic!("Failed to load script source file.");
-                    }
+            if !watching {
+                if errors.is_empty() {
+                    errors = lock(&cfg, &packages[0].home, &runner, locked);
+                }
+                // A failed build must not leave the manifest of a previous one behind.
+                write_manifest(&runner, &cfg, &errors);
+                if !errors.is_empty() {
+                    exit_with_errors(&errors);
                 }
+                prune(&runner);
+                println_status!("Build finished.");
+                return;
             }
-            println!("Build finished.");
+
+            write_manifest(&runner, &cfg, &errors);
+            report(&errors);
+            let mut module_dirs: Vec<PathBuf> = packages.iter().map(|p| p.module_dir()).collect();
+            // Changes to a dependency rebuild the modules like changes to the packages do.
+            module_dirs.extend(dependency_dirs(&packages));
+            let script_dirs: Vec<PathBuf> = packages.iter().map(|p| p.script_dir()).collect();
+            let dirs: Vec<PathBuf> = module_dirs.iter().chain(&script_dirs).cloned().collect();
+            watch(&dirs, |changed| {
+                let modules_changed = touches(changed, &module_dirs);
+                if modules_changed {
+                    // Modules can not be replaced in the runner, start over.
+                    let previous = match MoveRunner::new(cfg.clone()) {
+                        Ok(new_runner) => std::mem::replace(&mut runner, new_runner),
+                        Err(errors) => return report(&errors),
+                    };
+                    errors = compile_modules(&packages, &mut runner);
+                    runner.keep_scripts(&previous);
+                } else {
+                    errors.clear();
+                }
+                for package in &packages {
+                    let scripts = if modules_changed {
+                        let scripts = source_files(&package.script_dir());
+                        affected_scripts(changed, &module_dirs, scripts)
+                    } else {
+                        changed_in(changed, &package.script_dir())
+                    };
+                    errors.extend(compile_scripts(&mut runner, package, scripts));
+                }
+                write_manifest(&runner, &cfg, &errors);
+                report(&errors);
+            });
+        }
+    }
+}
+
+fn compile_scripts(
+    runner: &mut MoveRunner,
+    package: &Config,
+    scripts: Vec<PathBuf>,
+) -> Vec<Diagnostic> {
+    // Scripts do not depend on each other, only their artifacts are written in order.
+    let built: Vec<_> = scripts
+        .par_iter()
+        .map(|path| runner.build_script(path, package.address()))
+        .collect();
+    let mut errors = vec![];
+    for (path, result) in scripts.iter().zip(built) {
+        println_color("Compiling");
+        print_status!("{:?}\n", &path.display());
+        if let Err(e) = result.and_then(|compiled| runner.add_script(compiled)) {
+            errors.extend(e);
         }
     }
+    errors
+}
+
+/// Remove the artifacts of deleted or renamed sources.
+fn prune(runner: &MoveRunner) {
+    for path in runner.prune_artifacts() {
+        println_color("Removed");
+        print_status!("{:?}\n", path.display());
+    }
+}
+
+/// Write the manifest of a successful build. A failed build removes the previous one, so
+/// that no tool picks up artifacts which are out of date.
+fn write_manifest(runner: &MoveRunner, cfg: &Config, errors: &[Diagnostic]) {
+    if !errors.is_empty() {
+        let _ = fs::remove_file(cfg.target_dir().join(MANIFEST_FILE));
+        return;
+    }
+    let manifest = runner.manifest(&cfg.home).save(&cfg.target_dir());
+    println_color("Writing");
+    print_status!("{:?}\n", manifest.display());
+}
+
+fn report(errors: &[Diagnostic]) {
+    errors.iter().for_each(emit);
+    if errors.is_empty() {
+        println_status!("Build finished.");
+    } else {
+        println_status!("Build failed with {} error(s).", errors.len());
+    }
 }
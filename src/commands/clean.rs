@@ -0,0 +1,44 @@
+use std::fs;
+
+use crate::{commands::Command, config::Config, Parameter, println_color};
+
+pub struct CleanCommand {}
+
+impl Command for CleanCommand {
+    fn execute(&self, params: Parameter) {
+        if let Parameter::Clean {
+            home,
+            keep_cache,
+            keep_txns,
+        } = params
+        {
+            let (cfg, _) = Config::load_workspace(home);
+            let target = cfg.target_dir();
+            let entries = match fs::read_dir(&target) {
+                Ok(entries) => entries,
+                Err(_) => {
+                    println_status!("Nothing to clean.");
+                    return;
+                }
+            };
+
+            println_color("Removing");
+            print_status!("{}\n", target.display());
+            for entry in entries.filter_map(Result::ok) {
+                // genesis.blob and Move.lock are in the project home and never removed.
+                if (keep_cache && entry.file_name() == "cache")
+                    || (keep_txns && entry.file_name() == "txns")
+                {
+                    continue;
+                }
+                let path = entry.path();
+                if path.is_dir() {
+                    fs::remove_dir_all(&path).expect("Failed to remove target directory");
+                } else {
+                    fs::remove_file(&path).expect("Failed to remove target file");
+                }
+            }
+            println_status!("Clean finished.");
+        }
+    }
+}
@@ -1,4 +1,12 @@
+use std::{collections::HashSet, fs, path::Path};
+
 use crate::{commands::Command, config::Config, Parameter, println_color, runner::MoveRunner};
+use crate::commands::{
+    compile_dependencies, compile_module_sources, exit_with_errors, module_sources,
+    unwrap_or_exit,
+};
+use crate::dependency::required_modules;
+use crate::outline::outline;
 
 pub struct CompileCommand {}
 
@@ -8,28 +16,58 @@ impl Command for CompileCommand {
             home,
             mut source_path,
             module,
+            ..
         } = params
         {
             let cfg = Config::load_config(home);
-            let mut m_runner = MoveRunner::new(cfg.clone());
+            let mut m_runner = unwrap_or_exit(MoveRunner::new(cfg.clone()));
 
             if module {
                 if !source_path.exists() {
                     source_path = cfg.module_dir().join(source_path);
                 }
+                compile_imports(&cfg, &mut m_runner, &source_path);
                 println_color("Compiling");
-                print!("{:?}\n", &source_path.display());
-                m_runner.complie_module(&source_path);
+                print_status!("{:?}\n", &source_path.display());
+                unwrap_or_exit(m_runner.complie_module(&source_path));
             } else {
                 if !source_path.exists() {
                     source_path = cfg.script_dir().join(source_path);
                 }
+                compile_imports(&cfg, &mut m_runner, &source_path);
                 println_color("Compiling");
-                print!("{:?}\n", &source_path.display());
-                m_runner.complie_script(&source_path);
+                print_status!("{:?}\n", &source_path.display());
+                unwrap_or_exit(m_runner.complie_script(&source_path));
             }
 
-            println!("Compile finished.");
+            println_status!("Compile finished.");
         }
     }
 }
+
+/// Compile the project modules imported by `source_path`, directly or indirectly, and
+/// the dependencies of the project.
+fn compile_imports(cfg: &Config, runner: &mut MoveRunner, source_path: &Path) {
+    let errors = compile_dependencies(cfg, runner, &mut HashSet::new());
+    if !errors.is_empty() {
+        exit_with_errors(&errors);
+    }
+
+    let imports: Vec<String> = outline(&fs::read_to_string(source_path).unwrap_or_default())
+        .imports
+        .into_iter()
+        .map(|i| i.module)
+        .collect();
+    let modules = module_sources(cfg).unwrap_or_else(|errors| exit_with_errors(&errors));
+    let target = fs::canonicalize(source_path).ok();
+    let required: Vec<_> = required_modules(&modules, &imports)
+        .into_iter()
+        .filter(|m| fs::canonicalize(&m.path).ok() != target)
+        .collect();
+
+    let address = cfg.address();
+    let errors = compile_module_sources(runner, required, |_| address);
+    if !errors.is_empty() {
+        exit_with_errors(&errors);
+    }
+}
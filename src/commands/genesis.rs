@@ -0,0 +1,17 @@
+use crate::{commands::Command, config::Config, Parameter, println_color};
+
+pub struct GenesisCommand {}
+
+impl Command for GenesisCommand {
+    fn execute(&self, params: Parameter) {
+        if let Parameter::Genesis { home } = params {
+            let cfg = Config::load_config(home);
+            cfg.genesis();
+            for (alias, account) in cfg.genesis_accounts() {
+                println_color("Funded");
+                print_status!("{} 0x{:x} with {}\n", alias, account.address, account.balance);
+            }
+            println_status!("'genesis.blob' generated in {:?}", &cfg.home);
+        }
+    }
+}
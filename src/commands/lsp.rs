@@ -0,0 +1,589 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+use vm::access::ModuleAccess;
+
+use crate::{commands::Command, config::Config, Parameter, runner::MoveRunner};
+use crate::commands::{compile_modules, source_files};
+use crate::diagnostics::{Diagnostic, line_col, reserve_stdout, Severity};
+use crate::outline::{Item, Outline, outline};
+
+pub struct LspCommand {}
+
+impl Command for LspCommand {
+    fn execute(&self, params: Parameter) {
+        if let Parameter::Lsp { home } = params {
+            // stdout carries the protocol, progress output must not end up there.
+            reserve_stdout();
+            Server::new(home).serve();
+        }
+    }
+}
+
+/// Members of a compiled dependency, used for completion of modules without sources.
+struct CompiledMembers {
+    name: String,
+    functions: Vec<String>,
+    structs: Vec<String>,
+}
+
+struct Server {
+    home: PathBuf,
+    cfg: Option<Config>,
+    /// Contents of the documents opened in the editor.
+    documents: HashMap<PathBuf, String>,
+    dependencies: Vec<CompiledMembers>,
+    /// Files with diagnostics in the last publish, so they can be cleared.
+    published: HashSet<PathBuf>,
+}
+
+/// A declaration the cursor can refer to.
+enum Target {
+    Source(PathBuf, Item),
+    Module(PathBuf, Item),
+    Compiled(String, String),
+}
+
+impl Server {
+    fn new(home: PathBuf) -> Self {
+        Self {
+            home,
+            cfg: None,
+            documents: HashMap::new(),
+            dependencies: vec![],
+            published: HashSet::new(),
+        }
+    }
+
+    fn serve(&mut self) {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        while let Some(message) = read_message(&mut input) {
+            let method = message["method"].as_str().unwrap_or("").to_string();
+            let params = &message["params"];
+            let result = match method.as_str() {
+                "initialize" => Some(self.initialize(params)),
+                "shutdown" => Some(Value::Null),
+                "exit" => return,
+                "textDocument/didOpen" => {
+                    let doc = &params["textDocument"];
+                    if let Some(path) = uri_to_path(&doc["uri"]) {
+                        let text = doc["text"].as_str().unwrap_or("").to_string();
+                        self.documents.insert(path, text);
+                    }
+                    self.check();
+                    None
+                }
+                "textDocument/didChange" => {
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+                    if let (Some(path), Some(text)) =
+                        (uri_to_path(&params["textDocument"]["uri"]), text)
+                    {
+                        self.documents.insert(path, text.to_string());
+                    }
+                    None
+                }
+                "textDocument/didSave" => {
+                    // The stdlib members are reloaded along with a custom stdlib.
+                    let reload = match (uri_to_path(&params["textDocument"]["uri"]), &self.cfg) {
+                        (Some(path), _) if path.ends_with("Move.toml") => true,
+                        (Some(path), Some(cfg)) => path.starts_with(cfg.custom_stdlib_dir()),
+                        _ => false,
+                    };
+                    if reload || self.cfg.is_none() {
+                        self.load();
+                    }
+                    self.check();
+                    None
+                }
+                "textDocument/didClose" => {
+                    if let Some(path) = uri_to_path(&params["textDocument"]["uri"]) {
+                        self.documents.remove(&path);
+                    }
+                    None
+                }
+                "textDocument/definition" => Some(self.definition(params)),
+                "textDocument/hover" => Some(self.hover(params)),
+                "textDocument/completion" => Some(self.completion(params)),
+                _ => None,
+            };
+
+            // Only requests carry an id and expect a response.
+            if !message["id"].is_null() {
+                write_message(&json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "result": result.unwrap_or(Value::Null),
+                }));
+            }
+        }
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        if let Some(root) = uri_to_path(&params["rootUri"]) {
+            self.home = root;
+        }
+        self.load();
+
+        json!({
+            "capabilities": {
+                "textDocumentSync": {
+                    "openClose": true,
+                    "change": 1,
+                    "save": { "includeText": false },
+                },
+                "definitionProvider": true,
+                "hoverProvider": true,
+                "completionProvider": { "triggerCharacters": ["."] },
+            }
+        })
+    }
+
+    /// Load the project config and the members of its compiled dependencies. Errors in
+    /// Move.toml are published instead of stopping the server.
+    fn load(&mut self) {
+        self.cfg = None;
+        if !self.home.join("Move.toml").exists() {
+            return;
+        }
+        let cfg = match Config::read(self.home.clone()) {
+            Ok(cfg) => cfg,
+            Err(diag) => return self.publish_all(vec![diag]),
+        };
+        let runner = match MoveRunner::new(cfg.clone()) {
+            Ok(runner) => runner,
+            Err(errors) => {
+                // A broken custom stdlib is reported like any other source of the project.
+                self.dependencies.clear();
+                self.cfg = Some(cfg);
+                return self.publish_all(errors);
+            }
+        };
+        self.dependencies = runner
+            .dependencies()
+            .iter()
+            .map(|m| {
+                let m = m.as_inner();
+                CompiledMembers {
+                    name: m.name().to_string(),
+                    functions: m
+                        .function_defs()
+                        .iter()
+                        .filter(|fdef| fdef.is_public())
+                        .map(|fdef| {
+                            let handle = m.function_handle_at(fdef.function);
+                            m.identifier_at(handle.name).to_string()
+                        })
+                        .collect(),
+                    structs: m
+                        .struct_defs()
+                        .iter()
+                        .map(|sdef| {
+                            let handle = m.struct_handle_at(sdef.struct_handle);
+                            m.identifier_at(handle.name).to_string()
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        self.cfg = Some(cfg);
+    }
+
+    /// Compile the project and publish diagnostics for every file.
+    fn check(&mut self) {
+        let diagnostics = match &self.cfg {
+            Some(cfg) => check_project(cfg),
+            None => return,
+        };
+        self.publish_all(diagnostics);
+    }
+
+    /// Publish diagnostics grouped by file, clearing the files which have none anymore.
+    fn publish_all(&mut self, diagnostics: Vec<Diagnostic>) {
+        let mut by_file: HashMap<PathBuf, Vec<Value>> = HashMap::new();
+        for diag in diagnostics {
+            if let Some(file) = &diag.file {
+                let text = self.text(file);
+                by_file
+                    .entry(file.clone())
+                    .or_default()
+                    .push(to_lsp_diagnostic(&diag, file, &text));
+            }
+        }
+
+        let stale: Vec<PathBuf> = self
+            .published
+            .iter()
+            .filter(|file| !by_file.contains_key(*file))
+            .cloned()
+            .collect();
+        for file in stale {
+            publish(&file, vec![]);
+        }
+        self.published = by_file.keys().cloned().collect();
+        for (file, diagnostics) in by_file {
+            publish(&file, diagnostics);
+        }
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        match self.resolve(params) {
+            Some(Target::Source(path, item)) | Some(Target::Module(path, item)) => {
+                let text = self.text(&path);
+                json!({
+                    "uri": path_to_uri(&path),
+                    "range": range(&text, item.offset, item.offset + item.name.len()),
+                })
+            }
+            _ => Value::Null,
+        }
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let contents = match self.resolve(params) {
+            Some(Target::Source(_, item)) => item.detail,
+            Some(Target::Module(_, item)) => format!("module {}", item.name),
+            Some(Target::Compiled(module, member)) if member.is_empty() => {
+                format!("module {}", module)
+            }
+            Some(Target::Compiled(module, member)) => format!("{}.{}", module, member),
+            None => return Value::Null,
+        };
+        json!({
+            "contents": { "kind": "markdown", "value": format!("```\n{}\n```", contents) }
+        })
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let (_, text, offset) = match self.position(params) {
+            Some(position) => position,
+            None => return Value::Null,
+        };
+        let current = outline(&text);
+        let (qualifier, _) = word_at(&text, offset);
+
+        let mut items = vec![];
+        match qualifier {
+            Some(ref qualifier) if qualifier == "Self" => {
+                items.extend(current.functions.iter().map(|f| item(&f.name, 3, &f.detail)));
+                items.extend(current.structs.iter().map(|s| item(&s.name, 22, &s.detail)));
+            }
+            Some(qualifier) => {
+                if let Some(import) = current.import(&qualifier) {
+                    if let Some((_, module)) = self.find_module(&import.module) {
+                        items.extend(module.functions.iter().map(|f| item(&f.name, 3, &f.detail)));
+                        items.extend(module.structs.iter().map(|s| item(&s.name, 22, &s.detail)));
+                    } else if let Some(compiled) = self.find_compiled(&import.module) {
+                        items.extend(compiled.functions.iter().map(|f| item(f, 3, "")));
+                        items.extend(compiled.structs.iter().map(|s| item(s, 22, "")));
+                    }
+                }
+            }
+            None => {
+                items.extend(current.imports.iter().map(|i| item(&i.alias, 9, &i.module)));
+                items.extend(current.functions.iter().map(|f| item(&f.name, 3, &f.detail)));
+                items.extend(current.structs.iter().map(|s| item(&s.name, 22, &s.detail)));
+            }
+        }
+        Value::Array(items)
+    }
+
+    /// Find the declaration referred to by the identifier under the cursor.
+    fn resolve(&self, params: &Value) -> Option<Target> {
+        let (path, text, offset) = self.position(params)?;
+        let current = outline(&text);
+        let (qualifier, word) = word_at(&text, offset);
+        let word = word?;
+
+        match qualifier {
+            Some(ref qualifier) if qualifier == "Self" => {
+                find_item(&current, &word).map(|item| Target::Source(path, item))
+            }
+            Some(qualifier) => {
+                let import = current.import(&qualifier)?;
+                match self.find_module(&import.module) {
+                    Some((module_path, module)) => find_item(&module, &word)
+                        .map(|item| Target::Source(module_path, item)),
+                    None => self
+                        .find_compiled(&import.module)
+                        .map(|compiled| Target::Compiled(compiled.name.clone(), word)),
+                }
+            }
+            None => {
+                if let Some(import) = current.import(&word) {
+                    return match self.find_module(&import.module) {
+                        Some((module_path, module)) => {
+                            module.module.map(|item| Target::Module(module_path, item))
+                        }
+                        None => Some(Target::Compiled(import.module.clone(), String::new())),
+                    };
+                }
+                find_item(&current, &word).map(|item| Target::Source(path, item))
+            }
+        }
+    }
+
+    /// The document, its text and the byte offset of the cursor of a position request.
+    fn position(&self, params: &Value) -> Option<(PathBuf, String, usize)> {
+        let path = uri_to_path(&params["textDocument"]["uri"])?;
+        let text = self.text(&path);
+        let line = params["position"]["line"].as_u64()? as usize;
+        let character = params["position"]["character"].as_u64()? as usize;
+        let offset = offset_at(&text, line, character);
+        Some((path, text, offset))
+    }
+
+    /// Search the project modules for a module declaration named `name`.
+    fn find_module(&self, name: &str) -> Option<(PathBuf, Outline)> {
+        let cfg = self.cfg.as_ref()?;
+        source_files(&cfg.module_dir()).into_iter().find_map(|path| {
+            let module = outline(&self.text(&path));
+            match &module.module {
+                Some(item) if item.name == name => Some((path, module)),
+                _ => None,
+            }
+        })
+    }
+
+    fn find_compiled(&self, name: &str) -> Option<&CompiledMembers> {
+        self.dependencies.iter().find(|m| m.name == name)
+    }
+
+    /// The text of a file, preferring the editor's version of open documents.
+    fn text(&self, path: &Path) -> String {
+        match self.documents.get(path) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(path).unwrap_or_default(),
+        }
+    }
+}
+
+/// Compile every module, script and test of the project, without writing any output.
+fn check_project(cfg: &Config) -> Vec<Diagnostic> {
+    let mut cfg = cfg.clone();
+    cfg.compile.output_move_bytecode = false;
+    cfg.compile.output_source_map = false;
+
+    let mut runner = match MoveRunner::new(cfg.clone()) {
+        Ok(runner) => runner,
+        Err(errors) => return errors,
+    };
+    // Modules are compiled after their dependencies and the modules they import.
+    let mut errors = compile_modules(&[cfg.clone()], &mut runner);
+    for path in source_files(&cfg.script_dir())
+        .into_iter()
+        .chain(source_files(&cfg.test_dir()))
+    {
+        if let Err(e) = runner.complie_script(&path) {
+            errors.extend(e);
+        }
+    }
+    errors
+}
+
+fn find_item(outline: &Outline, name: &str) -> Option<Item> {
+    outline
+        .functions
+        .iter()
+        .chain(outline.structs.iter())
+        .find(|item| item.name == name)
+        .cloned()
+}
+
+/// The identifier at `offset` and the module alias it is qualified with, ie: `Coin.pay`.
+fn word_at(text: &str, offset: usize) -> (Option<String>, Option<String>) {
+    let is_ident = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+    let bytes = text.as_bytes();
+    let offset = offset.min(bytes.len());
+    let mut start = offset;
+    while start > 0 && is_ident(bytes[start - 1]) {
+        start -= 1;
+    }
+    let mut end = offset;
+    while end < bytes.len() && is_ident(bytes[end]) {
+        end += 1;
+    }
+    let word = if start < end {
+        Some(text[start..end].to_string())
+    } else {
+        None
+    };
+
+    let qualifier = if start > 0 && bytes[start - 1] == b'.' {
+        let q_end = start - 1;
+        let mut q_start = q_end;
+        while q_start > 0 && is_ident(bytes[q_start - 1]) {
+            q_start -= 1;
+        }
+        if q_start < q_end {
+            Some(text[q_start..q_end].to_string())
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    (qualifier, word)
+}
+
+fn item(label: &str, kind: u8, detail: &str) -> Value {
+    json!({ "label": label, "kind": kind, "detail": detail })
+}
+
+fn to_lsp_diagnostic(diag: &Diagnostic, file: &Path, text: &str) -> Value {
+    let span = diag.span.clone().unwrap_or(0..0);
+    let range = range(text, span.start, span.end);
+    let severity = match diag.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    // Notes are shown next to the diagnostic, at its location.
+    let related: Vec<Value> = diag
+        .notes
+        .iter()
+        .chain(diag.trace.iter())
+        .map(|note| {
+            json!({
+                "location": { "uri": path_to_uri(file), "range": range },
+                "message": note,
+            })
+        })
+        .collect();
+    json!({
+        "range": range,
+        "severity": severity,
+        "code": diag.code,
+        "source": "move",
+        "message": diag.message,
+        "relatedInformation": related,
+    })
+}
+
+fn publish(file: &Path, diagnostics: Vec<Value>) {
+    write_message(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": path_to_uri(file), "diagnostics": diagnostics },
+    }));
+}
+
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json!({ "start": position(text, start), "end": position(text, end) })
+}
+
+/// LSP positions are 0-based, characters count UTF-16 code units.
+fn position(text: &str, offset: usize) -> Value {
+    let (line, _) = line_col(text, offset);
+    let line_start: usize = text.split('\n').take(line - 1).map(|l| l.len() + 1).sum();
+    let character: usize = text[line_start..]
+        .char_indices()
+        .take_while(|(i, _)| line_start + i < offset)
+        .map(|(_, c)| c.len_utf16())
+        .sum();
+    json!({ "line": line - 1, "character": character })
+}
+
+/// Byte offset of an LSP position.
+fn offset_at(text: &str, line: usize, character: usize) -> usize {
+    let line_start: usize = text.split('\n').take(line).map(|l| l.len() + 1).sum();
+    let line_start = line_start.min(text.len());
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn uri_to_path(uri: &Value) -> Option<PathBuf> {
+    let uri = uri.as_str()?.strip_prefix("file://")?;
+    // Percent-encoded bytes are UTF-8, decode them all before building the string.
+    let mut path = vec![];
+    let mut bytes = uri.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+            path.push(u8::from_str_radix(&hex, 16).ok()?);
+        } else {
+            path.push(b);
+        }
+    }
+    String::from_utf8(path).ok().map(PathBuf::from)
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = "file://".to_string();
+    for b in path.to_string_lossy().bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn write_message(message: &Value) {
+    let body = message.to_string();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let _ = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = out.flush();
+}
+
+#[test]
+fn test_word_at() {
+    let text = "x = HelloWorld.new(5);";
+    assert_eq!(
+        word_at(text, 16),
+        (Some("HelloWorld".to_string()), Some("new".to_string()))
+    );
+    assert_eq!(word_at(text, 5), (None, Some("HelloWorld".to_string())));
+}
+
+#[test]
+fn test_positions() {
+    let text = "a\n// é😀 x";
+    // `x` is at byte 12, after 7 UTF-16 code units of its line.
+    assert_eq!(position(text, 12), json!({ "line": 1, "character": 7 }));
+    assert_eq!(offset_at(text, 1, 7), 12);
+    assert_eq!(offset_at(text, 0, 10), 1);
+
+    let uri = json!("file:///tmp/my%20project/%C3%A9.mvir");
+    assert_eq!(uri_to_path(&uri), Some(PathBuf::from("/tmp/my project/é.mvir")));
+    assert_eq!(
+        path_to_uri(Path::new("/tmp/no such dir/é.mvir")),
+        "file:///tmp/no%20such%20dir/%C3%A9.mvir"
+    );
+}
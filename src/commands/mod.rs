@@ -1,19 +1,41 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glob::glob;
+use rayon::prelude::*;
 
 use libra_config::config::{ExecutionConfig, RootPath};
-use libra_types::transaction::{TransactionArgument, TransactionPayload};
+use libra_types::{
+    account_address::AccountAddress,
+    transaction::{parse_as_transaction_argument, TransactionArgument, TransactionPayload},
+};
+use move_core_types::language_storage::TypeTag;
 use move_vm_types::values::Value;
+use vm::access::ModuleAccess;
 
 use crate::{Parameter, println_color};
+use crate::commands::type_parser::parse_type_tags;
 use crate::config::Config;
-use crate::runner::MoveRunner;
+use crate::dependency::{self, DependencyError, layers, ModuleSource, sort_modules};
+use crate::diagnostics::{Diagnostic, emit};
+use crate::lockfile::{self, LOCK_FILE, LockedModule, Lockfile};
+use crate::runner::{CompileResult, is_source, MoveRunner};
 
 pub mod build;
+pub mod clean;
 pub mod compile;
+pub mod genesis;
+pub mod lsp;
 pub mod new;
+pub mod package;
 pub mod run;
 pub mod test;
+pub mod tx;
 pub mod type_parser;
+pub mod watch;
 
 pub trait Command {
     fn execute(&self, params: Parameter);
@@ -23,6 +45,10 @@ pub fn new_command() -> Box<dyn Command> {
     Box::new(new::NewCommand {})
 }
 
+pub fn genesis_command() -> Box<dyn Command> {
+    Box::new(genesis::GenesisCommand {})
+}
+
 pub fn compile_command() -> Box<dyn Command> {
     Box::new(compile::CompileCommand {})
 }
@@ -39,9 +65,25 @@ pub fn test_command() -> Box<dyn Command> {
     Box::new(test::TestCommand {})
 }
 
+pub fn package_command() -> Box<dyn Command> {
+    Box::new(package::PackageCommand {})
+}
+
+pub fn tx_command() -> Box<dyn Command> {
+    Box::new(tx::TxCommand {})
+}
+
+pub fn clean_command() -> Box<dyn Command> {
+    Box::new(clean::CleanCommand {})
+}
+
+pub fn lsp_command() -> Box<dyn Command> {
+    Box::new(lsp::LspCommand {})
+}
+
 fn load_genesis(cfg: &Config, runner: &mut MoveRunner) {
     println_color("Loading");
-    print!("'genesis.blob' from {:?}\n", &cfg.home);
+    print_status!("'genesis.blob' from {:?}\n", &cfg.home);
     let mut exec_cfg = ExecutionConfig::default();
     exec_cfg.genesis_file_location = PathBuf::from("genesis.blob");
     exec_cfg
@@ -52,13 +94,320 @@ fn load_genesis(cfg: &Config, runner: &mut MoveRunner) {
     let gen_payload = tx.as_signed_user_txn().unwrap().payload();
     match &gen_payload {
         TransactionPayload::WriteSet(cs) => {
+            let mismatches = runner.stdlib_mismatches(cs.write_set());
+            if !mismatches.is_empty() {
+                emit(
+                    &Diagnostic::warning(format!(
+                        "'genesis.blob' was generated from a different stdlib than the {:?} \
+                         one used for compiling",
+                        cfg.stdlib()
+                    ))
+                    .with_note(format!("differing modules: {}", mismatches.join(", ")))
+                    .with_note("genesis.blob has to be generated with the same `stdlib` setting"),
+                );
+            }
             runner.datastore.add_write_set(cs.write_set());
+            runner.republish_stdlib();
             //print_all(cs);
         }
-        TransactionPayload::Module(m) => println!("module:{:?}", m),
-        TransactionPayload::Script(s) => println!("script:{:?}", s),
-        TransactionPayload::Program => println!("unimplemented"),
+        TransactionPayload::Module(m) => println_status!("module:{:?}", m),
+        TransactionPayload::Script(s) => println_status!("script:{:?}", s),
+        TransactionPayload::Program => println_status!("unimplemented"),
+    }
+}
+
+/// All Move IR and Move sources under `dir`.
+fn source_files(dir: &Path) -> Vec<PathBuf> {
+    glob(&format!("{}/**/*", dir.display()))
+        .expect("Source directory is not valid.")
+        .map(|entry| entry.expect("Failed to load source file."))
+        .filter(|path| is_source(path))
+        .collect()
+}
+
+/// Module sources of the project, in the order they have to be compiled.
+fn module_sources(cfg: &Config) -> Result<Vec<ModuleSource>, Vec<Diagnostic>> {
+    sorted_sources(source_files(&cfg.module_dir()))
+}
+
+fn sorted_sources(paths: Vec<PathBuf>) -> Result<Vec<ModuleSource>, Vec<Diagnostic>> {
+    sort_modules(dependency::module_sources(paths)).map_err(|e| {
+        let file = match &e {
+            DependencyError::Cycle(modules) => modules[0].path.clone(),
+            DependencyError::Duplicate(_, _, path) => path.clone(),
+        };
+        vec![Diagnostic::error(e.to_string()).with_file(file)]
+    })
+}
+
+/// Compile every module of the packages and their dependencies and publish them into the
+/// data store. The modules of all packages are ordered together, so that packages of a
+/// workspace can import each other.
+fn compile_modules(packages: &[Config], runner: &mut MoveRunner) -> Vec<Diagnostic> {
+    // Packages depending on each other are compiled as part of the workspace.
+    let mut compiled: HashSet<PathBuf> = packages
+        .iter()
+        .map(|cfg| fs::canonicalize(&cfg.home).unwrap_or_else(|_| cfg.home.clone()))
+        .collect();
+    let mut errors = vec![];
+    for cfg in packages {
+        errors.extend(compile_dependencies(cfg, runner, &mut compiled));
+    }
+
+    let mut paths = vec![];
+    let mut addresses = HashMap::new();
+    for cfg in packages {
+        println_color("Loading");
+        print_status!("modules from {}\n", cfg.module_dir().display());
+        runner.add_package(cfg);
+        for path in source_files(&cfg.module_dir()) {
+            addresses.insert(path.clone(), cfg.address());
+            paths.push(path);
+        }
+    }
+    match sorted_sources(paths) {
+        Ok(modules) => {
+            errors.extend(compile_module_sources(runner, modules, |path| addresses[path]))
+        }
+        Err(e) => errors.extend(e),
+    }
+    errors
+}
+
+/// Compile the modules of the projects `cfg` depends on, under their own addresses and
+/// after their own dependencies. Projects in `compiled` are skipped.
+fn compile_dependencies(
+    cfg: &Config,
+    runner: &mut MoveRunner,
+    compiled: &mut HashSet<PathBuf>,
+) -> Vec<Diagnostic> {
+    let mut errors = vec![];
+    for (name, dependency) in &cfg.dependencies {
+        let (home, bytecode) = match (&dependency.path, &dependency.bytecode) {
+            (Some(path), None) => (cfg.home.join(path), false),
+            (None, Some(path)) => (cfg.home.join(path), true),
+            _ => {
+                let message = format!("dependency {} needs either a path or a bytecode", name);
+                errors.push(Diagnostic::error(message).with_file(cfg.home.join("Move.toml")));
+                continue;
+            }
+        };
+        if !compiled.insert(fs::canonicalize(&home).unwrap_or_else(|_| home.clone())) {
+            continue;
+        }
+        if bytecode {
+            errors.extend(load_bytecode(runner, name, &home));
+            continue;
+        }
+
+        let dep_cfg = match Config::read(home) {
+            Ok(dep_cfg) => dep_cfg,
+            Err(diag) => {
+                let note = format!("required by {}", cfg.home.join("Move.toml").display());
+                errors.push(diag.with_note(note));
+                continue;
+            }
+        };
+        errors.extend(compile_dependencies(&dep_cfg, runner, compiled));
+
+        println_color("Loading");
+        print_status!("dependency {} from {}\n", name, dep_cfg.module_dir().display());
+        runner.add_dependency(name, &dep_cfg);
+        match module_sources(&dep_cfg) {
+            Ok(modules) => {
+                let address = dep_cfg.address();
+                errors.extend(compile_module_sources(runner, modules, |_| address))
+            }
+            Err(e) => errors.extend(e),
+        }
     }
+    errors
+}
+
+/// Module directories of the source dependencies of the packages, directly or indirectly,
+/// for watching them along with the packages. Broken dependencies are reported by the build.
+fn dependency_dirs(packages: &[Config]) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    let mut pending: Vec<Config> = packages.to_vec();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    while let Some(cfg) = pending.pop() {
+        for dependency in cfg.dependencies.values() {
+            let home = match &dependency.path {
+                Some(path) => cfg.home.join(path),
+                None => continue,
+            };
+            if !seen.insert(fs::canonicalize(&home).unwrap_or_else(|_| home.clone())) {
+                continue;
+            }
+            if let Ok(dep_cfg) = Config::read(home) {
+                dirs.push(dep_cfg.module_dir());
+                pending.push(dep_cfg);
+            }
+        }
+    }
+    dirs
+}
+
+/// Load the precompiled modules of a dependency and publish them into the data store.
+fn load_bytecode(runner: &mut MoveRunner, name: &str, path: &Path) -> Vec<Diagnostic> {
+    println_color("Loading");
+    print_status!("dependency {} from {}\n", name, path.display());
+    let files = if path.is_dir() {
+        let mut files: Vec<_> = glob(&format!("{}/**/*.mv", path.display()))
+            .expect("Dependency directory is not valid.")
+            .filter_map(Result::ok)
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut errors = vec![];
+    for file in files {
+        match runner.load_module(&file) {
+            Ok(m) => {
+                let cm = m.as_inner();
+                runner.datastore.add_module(&cm.self_id(), cm);
+            }
+            Err(e) => errors.extend(e),
+        }
+    }
+    errors
+}
+
+/// Compile sorted modules and publish them into the data store. The modules of a
+/// dependency layer do not import each other and are compiled concurrently.
+fn compile_module_sources(
+    runner: &mut MoveRunner,
+    modules: Vec<ModuleSource>,
+    address: impl Fn(&Path) -> AccountAddress + Sync,
+) -> Vec<Diagnostic> {
+    let mut errors = vec![];
+    for layer in layers(modules) {
+        let built: Vec<_> = layer
+            .par_iter()
+            .map(|module| runner.build_module(&module.path, address(&module.path)))
+            .collect();
+        for (module, result) in layer.iter().zip(built) {
+            println_color("Compiling");
+            print_status!("{:?}\n", &module.path.display());
+            match result.and_then(|compiled| runner.add_module(compiled)) {
+                Ok(m) => {
+                    let cm = m.as_inner();
+                    runner.datastore.add_module(&cm.self_id(), cm);
+                }
+                Err(e) => errors.extend(e),
+            }
+        }
+    }
+    errors
+}
+
+/// Record the build in the Move.lock of `cfg`, or in locked mode check that it matches.
+/// `genesis_home` is where the `genesis.blob` of the build is.
+fn lock(cfg: &Config, genesis_home: &Path, runner: &MoveRunner, locked: bool) -> Vec<Diagnostic> {
+    let mut modules: Vec<LockedModule> = runner
+        .compiled_modules()
+        .into_iter()
+        .map(|(path, module)| {
+            let mut bytes = vec![];
+            module.serialize(&mut bytes).expect("Unable to serialize module");
+            LockedModule {
+                source: path.strip_prefix(&cfg.home).unwrap_or(path).to_path_buf(),
+                name: module.name().to_string(),
+                address: *module.address(),
+                hash: lockfile::hash(&bytes),
+            }
+        })
+        .collect();
+    modules.sort_by(|a, b| a.source.cmp(&b.source));
+    let built = Lockfile {
+        stdlib: cfg.stdlib(),
+        stdlib_hash: runner.stdlib_hash().to_string(),
+        genesis: fs::read(genesis_home.join("genesis.blob"))
+            .ok()
+            .map(|bytes| lockfile::hash(&bytes)),
+        modules,
+    };
+
+    let lock_path = cfg.home.join(LOCK_FILE);
+    let lockfile = match Lockfile::load(&cfg.home) {
+        Ok(lockfile) => lockfile,
+        Err(diag) => return vec![diag],
+    };
+    match lockfile {
+        Some(lockfile) if locked => {
+            let diffs = lockfile.diff(&built);
+            if diffs.is_empty() {
+                return vec![];
+            }
+            let mut diag =
+                Diagnostic::error("the build does not match Move.lock").with_file(lock_path);
+            for diff in diffs {
+                diag = diag.with_note(diff);
+            }
+            vec![diag]
+        }
+        None if locked => {
+            let message = "Move.lock is missing, build once without --locked";
+            vec![Diagnostic::error(message).with_file(lock_path)]
+        }
+        _ => {
+            built.save(&cfg.home);
+            vec![]
+        }
+    }
+}
+
+/// Print compile errors and stop, nothing can run without the failed module or script.
+fn unwrap_or_exit<T>(result: CompileResult<T>) -> T {
+    result.unwrap_or_else(|errors| exit_with_errors(&errors))
+}
+
+fn exit_with_errors(errors: &[Diagnostic]) -> ! {
+    for diag in errors {
+        emit(diag);
+    }
+    println_status!("Compilation failed with {} error(s).", errors.len());
+    std::process::exit(1)
+}
+
+/// Parse the arguments of a script, `@alice` is the address of a named account or address.
+fn parse_txn_args(
+    cfg: &Config,
+    addresses: &BTreeMap<String, AccountAddress>,
+    args: &[String],
+) -> Vec<TransactionArgument> {
+    args.iter()
+        .map(|arg| {
+            if let Some(name) = arg.strip_prefix('@') {
+                let address = addresses.get(name).unwrap_or_else(|| {
+                    let message = format!("no account or address named {}", name);
+                    let diag = Diagnostic::error(message).with_file(cfg.home.join("Move.toml"));
+                    exit_with_errors(&[diag])
+                });
+                TransactionArgument::Address(*address)
+            } else {
+                parse_as_transaction_argument(arg).unwrap_or_else(|e| {
+                    let diag = Diagnostic::error(format!("invalid argument {}: {}", arg, e));
+                    exit_with_errors(&[diag])
+                })
+            }
+        })
+        .collect()
+}
+
+/// Parse the type arguments of a script, `{{name}}` addresses are looked up in `addresses`.
+fn parse_type_args(
+    addresses: &BTreeMap<String, AccountAddress>,
+    type_args: &[String],
+) -> Vec<TypeTag> {
+    let type_args = type_args.join(",");
+    parse_type_tags(&type_args, addresses).unwrap_or_else(|e| {
+        let diag = Diagnostic::error(format!("invalid type arguments {}: {}", type_args, e));
+        exit_with_errors(&[diag])
+    })
 }
 
 /// Convert the transaction arguments into move values.
@@ -8,7 +8,7 @@ impl Command for NewCommand {
             let x = Config::new(name.clone(), home);
             x.initial();
             x.genesis();
-            println!("Move project[{}] created successfully, Move now!", name);
+            println_status!("Move project[{}] created successfully, Move now!", name);
         }
     }
 }
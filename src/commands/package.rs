@@ -0,0 +1,75 @@
+use std::{collections::HashMap, fs};
+
+use libra_types::{
+    account_address::AccountAddress,
+    transaction::{Module, RawTransaction},
+};
+use vm::access::ModuleAccess;
+
+use crate::{commands::Command, config::Config, Parameter, println_color, runner::MoveRunner};
+use crate::commands::{compile_modules, exit_with_errors, unwrap_or_exit};
+
+pub struct PackageCommand {}
+
+impl Command for PackageCommand {
+    fn execute(&self, params: Parameter) {
+        if let Parameter::Package { home, txn } = params {
+            let (cfg, packages) = Config::load_workspace(home);
+            let mut runner = unwrap_or_exit(MoveRunner::new(cfg.clone()));
+            let errors = compile_modules(&packages, &mut runner);
+            if !errors.is_empty() {
+                exit_with_errors(&errors);
+            }
+
+            // A package replaces the previous one, the transactions of `move tx` are kept.
+            let dir = cfg.target_dir().join("txns").join("package");
+            if dir.exists() {
+                fs::remove_dir_all(&dir).expect("Failed to remove txns directory");
+            }
+            fs::create_dir_all(&dir).expect("Failed to create txns directory");
+
+            let mut sequence_numbers: HashMap<AccountAddress, u64> = packages
+                .iter()
+                .rev()
+                .map(|package| (package.address(), package.tx.sequence_number))
+                .collect();
+            let expiration_time = txn.expiration_time();
+            let mut count = 0;
+            // Compiled modules come after the modules they import, in publishing order.
+            for (path, module) in runner.compiled_modules() {
+                // Only the members' own modules are packaged, dependencies are packaged from
+                // their own projects.
+                let sender = match packages.iter().find(|p| path.starts_with(p.module_dir())) {
+                    Some(sender) => sender,
+                    None => continue,
+                };
+                let sequence_number = sequence_numbers
+                    .get_mut(&sender.address())
+                    .expect("Unknown sender");
+
+                let mut code = vec![];
+                module.serialize(&mut code).expect("Unable to serialize module");
+                let raw_txn = RawTransaction::new_module(
+                    sender.address(),
+                    *sequence_number,
+                    Module::new(code),
+                    txn.max_gas_amount,
+                    txn.gas_unit_price,
+                    expiration_time,
+                );
+                *sequence_number += 1;
+
+                let signed_txn = sender
+                    .sign(raw_txn)
+                    .unwrap_or_else(|diag| exit_with_errors(&[diag]));
+                let bytes = lcs::to_bytes(&signed_txn).expect("Unable to serialize transaction");
+                let path = dir.join(format!("{:03}_{}.txn", count, module.name()));
+                fs::write(&path, bytes).expect("Failed to write transaction");
+                println_color("Packaging");
+                print_status!("{:?}\n", path.display());
+                count += 1;
+            }
+            println_status!("Package finished, {} transaction(s) written.", count);
+        }
+    }
+}
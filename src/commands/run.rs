@@ -1,5 +1,4 @@
-use bytecode_verifier::verifier::VerifiedModule;
-use libra_types::transaction::{parse_as_transaction_argument, TransactionArgument};
+use libra_types::transaction::TransactionArgument;
 use move_core_types::gas_schedule::{GasAlgebra, GasUnits};
 use move_core_types::language_storage::TypeTag;
 use move_vm_runtime::MoveVM;
@@ -8,11 +7,12 @@ use move_vm_types::gas_schedule::zero_cost_schedule;
 use move_vm_types::transaction_metadata::TransactionMetadata;
 use vm::errors::VMResult;
 
-use glob::glob;
-
 use crate::{commands::Command, config::Config, Parameter, println_color, runner::MoveRunner};
-use crate::commands::{convert_txn_args, load_genesis};
-use crate::commands::type_parser::parse_type_tags;
+use crate::commands::{
+    compile_modules, convert_txn_args, exit_with_errors, load_genesis, parse_txn_args,
+    parse_type_args, unwrap_or_exit,
+};
+use crate::diagnostics::emit;
 
 pub struct RunCommand {}
 
@@ -23,36 +23,26 @@ impl Command for RunCommand {
             mut source_path,
             type_args,
             args,
+            sender,
+            ..
         } = params
         {
-            let ty_args: Vec<TypeTag> = parse_type_tags(&type_args.join(",")).unwrap();
+            let cfg = Config::load_config(home);
+            let sender = cfg
+                .sender(sender.as_deref())
+                .unwrap_or_else(|diag| exit_with_errors(&[diag]));
+            let mut addresses = cfg.addresses();
+            addresses.insert("sender".to_string(), sender);
+            let ty_args: Vec<TypeTag> = parse_type_args(&addresses, &type_args);
 
             // check if arguments are valid.
-            let ta_args: Vec<TransactionArgument> = args
-                .iter()
-                .map(|arg| parse_as_transaction_argument(arg).unwrap())
-                .collect();
+            let ta_args: Vec<TransactionArgument> = parse_txn_args(&cfg, &addresses, &args);
             let va_args = convert_txn_args(&ta_args);
 
-            let cfg = Config::load_config(home);
-            let mut m_runner = MoveRunner::new(cfg.clone());
-            println_color("Loading");
-            print!("modules from {}\n", &cfg.module_dir().display());
-            let mdir = glob(&format!("{}/**/*.mvir", &cfg.module_dir().display()))
-                .expect("Module directory is not valid.");
-            for entry in mdir {
-                match entry {
-                    Ok(path) => {
-                        println_color("Compiling");
-                        print!("{:?}\n", &path.display());
-                        let m: VerifiedModule = m_runner.complie_module(&path);
-                        let cm = &m.as_inner();
-                        m_runner.datastore.add_module(&cm.self_id(), cm);
-                    }
-                    Err(_) => {
-                        panic!("Failed to load modules source file.");
-                    }
-                }
+            let mut m_runner = unwrap_or_exit(MoveRunner::new(cfg.clone()));
+            let errors = compile_modules(&[cfg.clone()], &mut m_runner);
+            if !errors.is_empty() {
+                exit_with_errors(&errors);
             }
 
             if !source_path.exists() {
@@ -60,13 +50,14 @@ impl Command for RunCommand {
             }
 
             println_color("Compiling");
-            print!("{:?}\n", &source_path.display());
-            let compiled_script = m_runner.complie_script(&source_path).into_inner();
+            print_status!("{:?}\n", &source_path.display());
+            let compiled_script =
+                unwrap_or_exit(m_runner.complie_script_as(&source_path, sender)).into_inner();
 
             load_genesis(&cfg, &mut m_runner);
 
             println_color("Running");
-            print!(
+            print_status!(
                 "Script: {:?} Type Args:{:?}, Args: {:?}\n",
                 &source_path.file_name().unwrap(),
                 &ty_args,
@@ -86,7 +77,7 @@ impl Command for RunCommand {
             let gas_schedule = zero_cost_schedule();
 
             let mut txn_data = TransactionMetadata::default();
-            txn_data.sender = cfg.address();
+            txn_data.sender = sender;
 
             let result: VMResult<()> =
                 move_vm.execute_script(script, &gas_schedule, &mut ctx, &txn_data, ty_args, va_args);
@@ -95,14 +86,14 @@ impl Command for RunCommand {
                 Ok(_) => {
                     let ws = ctx.make_write_set().unwrap();
                     println_color("Output");
-                    print!("{} WriteSet was generated\n", &ws.len());
+                    print_status!("{} WriteSet was generated\n", &ws.len());
 
                     for (a, wo) in ws {
-                        println!("AccessPath:{}, {:?}", a, wo);
+                        println_status!("AccessPath:{}, {:?}", a, wo);
                     }
-                    println!("The script runs successfully")
+                    println_status!("The script runs successfully")
                 }
-                Err(e) => println!("Error: {:?}", e),
+                Err(e) => emit(&m_runner.report_vm_error(&e, &source_path)),
             }
         }
     }
@@ -1,107 +1,188 @@
 use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use bytecode_verifier::verifier::VerifiedModule;
+use bytecode_verifier::verifier::VerifiedScript;
+use libra_types::account_address::AccountAddress;
 use move_core_types::gas_schedule::{GasAlgebra, GasUnits};
 use move_vm_runtime::MoveVM;
 use move_vm_state::execution_context::TransactionExecutionContext;
 use move_vm_types::gas_schedule::zero_cost_schedule;
 use move_vm_types::transaction_metadata::TransactionMetadata;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use rayon::prelude::*;
+use termcolor::{Color, ColorSpec, WriteColor};
 use vm::errors::VMResult;
 
-use glob::glob;
-
-use crate::{commands::Command, config::Config, Parameter, println_color, runner::MoveRunner};
-use crate::commands::load_genesis;
+use crate::{commands::Command, config::Config, Parameter, println_color};
+use crate::commands::{
+    compile_modules, dependency_dirs, exit_with_errors, load_genesis, lock, source_files,
+    unwrap_or_exit,
+};
+use crate::commands::watch::{affected_scripts, changed_in, touches, watch};
+use crate::diagnostics::{emit, emit_test_result, status_stream};
+use crate::runner::{Compiled, CompileResult, MoveRunner};
 
 pub struct TestCommand {}
 
 impl Command for TestCommand {
     fn execute(&self, params: Parameter) {
-        if let Parameter::Test { home } = params {
+        if let Parameter::Test {
+            home,
+            watch: watching,
+            locked,
+            sender,
+            ..
+        } = params
+        {
             // initialize
-            let cfg = Config::load_config(home);
-            let mut m_runner = MoveRunner::new(cfg.clone());
-            load_genesis(&cfg, &mut m_runner);
+            let (cfg, packages) = Config::load_workspace(home);
+            let sender = sender.map(|alias| named_sender(&packages, &alias));
+            let mut m_runner = unwrap_or_exit(MoveRunner::new(cfg.clone()));
+            load_genesis(&packages[0], &mut m_runner);
 
             // loading dependencies
-            println_color("Loading");
-            print!("modules from {}\n", &cfg.module_dir().display());
-            let mdir = glob(&format!("{}/**/*.mvir", &cfg.module_dir().display()))
-                .expect("Module directory is not valid.");
-            for entry in mdir {
-                match entry {
-                    Ok(path) => {
-                        println_color("Compiling");
-                        print!("{:?}\n", &path.display());
-                        let m: VerifiedModule = m_runner.complie_module(&path);
-                        let cm = &m.as_inner();
-                        m_runner.datastore.add_module(&cm.self_id(), cm);
-                    }
-                    Err(_) => {
-                        panic!("Failed to load modules source file.");
-                    }
-                }
+            let mut errors = compile_modules(&packages, &mut m_runner);
+            if errors.is_empty() && !watching {
+                errors = lock(&cfg, &packages[0].home, &m_runner, locked);
             }
+            if !errors.is_empty() && !watching {
+                exit_with_errors(&errors);
+            }
+            errors.iter().for_each(emit);
 
             // loading test cases
-            println_color("Loading");
-            print!("test cases from {}\n", &cfg.test_dir().display());
-            let mdir = glob(&format!("{}/**/*.mvir", &cfg.test_dir().display()))
-                .expect("Module directory is not valid.");
-            for entry in mdir {
-                match entry {
-                    Ok(path) => {
-                        println_color("Compiling");
-                        print!("{:?}\n", &path);
-                        let compiled_script = m_runner.complie_script(&path).into_inner();
-
-                        println_color("Running");
-                        print!("Script: {:?} Args: []", &path.file_name().unwrap());
-
-                        let mut script: Vec<u8> = vec![];
-                        compiled_script
-                            .as_inner()
-                            .serialize(&mut script)
-                            .expect("Unable to serialize script");
-
-                        // Execute script.
-                        // create a Move VM and populate it with generated modules
-                        let move_vm = MoveVM::new();
-                        let mut ctx = TransactionExecutionContext::new(
-                            GasUnits::new(600),
-                            &m_runner.datastore,
-                        );
-                        let gas_schedule = zero_cost_schedule();
-
-                        let mut txn_data = TransactionMetadata::default();
-                        txn_data.sender = cfg.address();
-
-                        let result: VMResult<()> = move_vm.execute_script(
-                            script,
-                            &gas_schedule,
-                            &mut ctx,
-                            &txn_data,
-                            vec![],
-                            vec![],
-                        );
-
-                        match result {
-                            Ok(_) => status_print("OK\n", Color::Green),
-                            Err(_e) => status_print("Failed\n", Color::Red),
+            for package in &packages {
+                println_color("Loading");
+                print_status!("test cases from {}\n", &package.test_dir().display());
+                if errors.is_empty() {
+                    let tests = source_files(&package.test_dir());
+                    run_tests(package, sender, &mut m_runner, tests);
+                }
+            }
+
+            if watching {
+                let mut module_dirs: Vec<PathBuf> =
+                    packages.iter().map(|p| p.module_dir()).collect();
+                // Changes to a dependency rebuild the modules like changes to the packages do.
+                module_dirs.extend(dependency_dirs(&packages));
+                let test_dirs: Vec<PathBuf> = packages.iter().map(|p| p.test_dir()).collect();
+                let dirs: Vec<PathBuf> = module_dirs.iter().chain(&test_dirs).cloned().collect();
+                watch(&dirs, |changed| {
+                    let modules_changed = touches(changed, &module_dirs);
+                    if modules_changed {
+                        // Modules can not be replaced in the runner, start over.
+                        m_runner = match MoveRunner::new(cfg.clone()) {
+                            Ok(runner) => runner,
+                            Err(errors) => {
+                                errors.iter().for_each(emit);
+                                return;
+                            }
+                        };
+                        load_genesis(&packages[0], &mut m_runner);
+                        let errors = compile_modules(&packages, &mut m_runner);
+                        if !errors.is_empty() {
+                            errors.iter().for_each(emit);
+                            return;
                         }
                     }
-                    Err(_) => {
-                        panic!("Failed to load source file of test cases.");
+                    for package in &packages {
+                        let tests = if modules_changed {
+                            let tests = source_files(&package.test_dir());
+                            affected_scripts(changed, &module_dirs, tests)
+                        } else {
+                            changed_in(changed, &package.test_dir())
+                        };
+                        run_tests(package, sender, &mut m_runner, tests);
                     }
-                }
-            } // for
+                });
+            }
+        }
+    }
+}
+
+/// Address of the account named `alias`, which can be declared by any package of the
+/// workspace.
+fn named_sender(packages: &[Config], alias: &str) -> AccountAddress {
+    packages
+        .iter()
+        .find_map(|package| package.sender(Some(alias)).ok())
+        .unwrap_or_else(|| {
+            let diag = packages[0].sender(Some(alias)).unwrap_err();
+            exit_with_errors(&[diag])
+        })
+}
+
+/// Compile the test scripts concurrently, then run them one by one. They are sent from
+/// `sender` if given, or else from the package address.
+fn run_tests(
+    cfg: &Config,
+    sender: Option<AccountAddress>,
+    m_runner: &mut MoveRunner,
+    tests: Vec<PathBuf>,
+) {
+    let sender = sender.unwrap_or_else(|| cfg.address());
+    let built: Vec<_> = tests
+        .par_iter()
+        .map(|path| m_runner.build_script(path, sender))
+        .collect();
+    for (path, result) in tests.iter().zip(built) {
+        run_test(sender, m_runner, path, result);
+    }
+}
+
+fn run_test(
+    sender: AccountAddress,
+    m_runner: &mut MoveRunner,
+    path: &Path,
+    built: CompileResult<Compiled<VerifiedScript>>,
+) {
+    println_color("Compiling");
+    print_status!("{:?}\n", path);
+    let compiled_script = match built.and_then(|compiled| m_runner.add_script(compiled)) {
+        Ok(script) => script.into_inner(),
+        Err(errors) => {
+            status_print("Failed\n", Color::Red);
+            emit_test_result(path, false);
+            errors.iter().for_each(emit);
+            return;
+        }
+    };
+
+    println_color("Running");
+    print_status!("Script: {:?} Args: []", &path.file_name().unwrap());
+
+    let mut script: Vec<u8> = vec![];
+    compiled_script
+        .as_inner()
+        .serialize(&mut script)
+        .expect("Unable to serialize script");
+
+    // Execute script.
+    // create a Move VM and populate it with generated modules
+    let move_vm = MoveVM::new();
+    let mut ctx = TransactionExecutionContext::new(GasUnits::new(600), &m_runner.datastore);
+    let gas_schedule = zero_cost_schedule();
+
+    let mut txn_data = TransactionMetadata::default();
+    txn_data.sender = sender;
+
+    let result: VMResult<()> =
+        move_vm.execute_script(script, &gas_schedule, &mut ctx, &txn_data, vec![], vec![]);
+
+    match result {
+        Ok(_) => {
+            status_print("OK\n", Color::Green);
+            emit_test_result(path, true);
+        }
+        Err(e) => {
+            status_print("Failed\n", Color::Red);
+            emit_test_result(path, false);
+            emit(&m_runner.report_vm_error(&e, path));
         }
     }
 }
 
 fn status_print(content: &'static str, color: Color) {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    let mut stdout = status_stream();
     let _ = stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true));
     let _ = write!(&mut stdout, "{:>12} ", content);
     let _ = stdout.reset();
@@ -0,0 +1,210 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libra_types::{
+    account_address::from_public_key,
+    transaction::{
+        RawTransaction, Script, SignedTransaction, Transaction,
+        TransactionArgument, TransactionPayload,
+    },
+    write_set::WriteOp,
+};
+use move_core_types::language_storage::TypeTag;
+use vm::{
+    access::ModuleAccess,
+    file_format::{CompiledModule, CompiledScript},
+};
+
+use crate::{commands::Command, config::Config, Parameter, println_color, runner::MoveRunner};
+use crate::{TxnOptions, TxParameter};
+use crate::commands::{
+    compile_modules, exit_with_errors, parse_txn_args, parse_type_args, unwrap_or_exit,
+};
+use crate::diagnostics::{Diagnostic, emit};
+use crate::lockfile;
+use crate::runner::artifact_file;
+
+pub struct TxCommand {}
+
+impl Command for TxCommand {
+    fn execute(&self, params: Parameter) {
+        if let Parameter::Tx { cmd } = params {
+            match cmd {
+                TxParameter::Script {
+                    home,
+                    source_path,
+                    type_args,
+                    args,
+                    output,
+                    txn,
+                } => sign_script(home, source_path, &type_args, &args, output, &txn),
+                TxParameter::Decode { input } => decode(&input),
+            }
+        }
+    }
+}
+
+/// Compile a script and write a signed transaction running it.
+fn sign_script(
+    home: PathBuf,
+    mut source_path: PathBuf,
+    type_args: &[String],
+    args: &[String],
+    output: Option<PathBuf>,
+    txn: &TxnOptions,
+) {
+    let cfg = Config::load_config(home);
+    if !source_path.exists() {
+        source_path = cfg.script_dir().join(source_path);
+    }
+    // Transactions are sent from the transaction account.
+    let mut addresses = cfg.addresses();
+    addresses.insert("sender".to_string(), cfg.address());
+    let ty_args: Vec<TypeTag> = parse_type_args(&addresses, type_args);
+    let ta_args: Vec<TransactionArgument> = parse_txn_args(&cfg, &addresses, args);
+
+    let mut runner = unwrap_or_exit(MoveRunner::new(cfg.clone()));
+    let errors = compile_modules(&[cfg.clone()], &mut runner);
+    if !errors.is_empty() {
+        exit_with_errors(&errors);
+    }
+    println_color("Compiling");
+    print_status!("{:?}\n", &source_path.display());
+    let compiled_script = unwrap_or_exit(runner.complie_script(&source_path));
+
+    let mut code = vec![];
+    compiled_script
+        .as_inner()
+        .serialize(&mut code)
+        .expect("Unable to serialize script");
+    let raw_txn = RawTransaction::new_script(
+        cfg.address(),
+        cfg.tx.sequence_number,
+        Script::new(code, ty_args, ta_args),
+        txn.max_gas_amount,
+        txn.gas_unit_price,
+        txn.expiration_time(),
+    );
+    let signed_txn = cfg.sign(raw_txn).unwrap_or_else(|diag| exit_with_errors(&[diag]));
+    let bytes = lcs::to_bytes(&signed_txn).expect("Unable to serialize transaction");
+
+    let path = output.unwrap_or_else(|| {
+        // Like the compiled scripts, `a.b.mvir` is signed to `a.b.txn`.
+        let name = source_path.file_stem().unwrap();
+        artifact_file(&cfg.target_dir().join("txns").join(name), "txn")
+    });
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("Failed to create txns directory");
+    }
+    fs::write(&path, &bytes).expect("Failed to write transaction");
+    println_color("Signed");
+    print_status!("{:?}\n", path.display());
+    println!("{}", hex::encode(&bytes));
+}
+
+/// Print a signed transaction, a raw transaction or a genesis blob, given as a file or hex.
+fn decode(input: &str) {
+    let bytes = if Path::new(input).exists() {
+        fs::read(input).expect("Failed to read transaction file")
+    } else {
+        hex::decode(input.trim_start_matches("0x")).unwrap_or_else(|_| {
+            fail(format!("{} is neither a file nor hex encoded bytes", input))
+        })
+    };
+
+    if let Ok(signed_txn) = lcs::from_bytes::<SignedTransaction>(&bytes) {
+        print_signed(&signed_txn);
+    } else if let Ok(txn) = lcs::from_bytes::<Transaction>(&bytes) {
+        match txn {
+            Transaction::UserTransaction(signed_txn) => print_signed(&signed_txn),
+            other => println!("{:#?}", other),
+        }
+    } else if let Ok(raw_txn) = lcs::from_bytes::<RawTransaction>(&bytes) {
+        println!("Unsigned transaction");
+        println!("{:#?}", raw_txn);
+        print_payload(&raw_txn.into_payload());
+    } else {
+        fail("not a signed transaction, a raw transaction or a genesis blob".to_string());
+    }
+}
+
+fn print_signed(txn: &SignedTransaction) {
+    // A valid signature only proves the key signed the transaction, the key also has to
+    // be the one of the sender.
+    let signature = match txn.check_signature() {
+        Err(e) => format!("INVALID, {}", e),
+        Ok(_) if from_public_key(&txn.public_key()) != txn.sender() => {
+            "INVALID, the public key is not the key of the sender".to_string()
+        }
+        Ok(_) => "valid".to_string(),
+    };
+    println!("Sender:          0x{:x}", txn.sender());
+    println!("Sequence number: {}", txn.sequence_number());
+    println!("Max gas amount:  {}", txn.max_gas_amount());
+    println!("Gas unit price:  {}", txn.gas_unit_price());
+    println!("Expiration time: {}", txn.expiration_time().as_secs());
+    println!("Public key:      {}", txn.public_key());
+    println!("Signature:       {}", signature);
+    print_payload(txn.payload());
+}
+
+fn print_payload(payload: &TransactionPayload) {
+    match payload {
+        TransactionPayload::Script(script) => {
+            println!("Payload:         script, {} bytes", script.code().len());
+            println!("Hash:            {}", lockfile::hash(script.code()));
+            println!("Type args:       {:?}", script.ty_args());
+            println!("Args:            {:?}", script.args());
+            match CompiledScript::deserialize(script.code()) {
+                Ok(compiled) => print_code(&compiled.into_module()),
+                Err(e) => println!("Invalid script bytecode: {:?}", e),
+            }
+        }
+        TransactionPayload::Module(module) => {
+            println!("Payload:         module, {} bytes", module.code().len());
+            println!("Hash:            {}", lockfile::hash(module.code()));
+            match CompiledModule::deserialize(module.code()) {
+                Ok(compiled) => {
+                    println!("Module:          0x{:x}::{}", compiled.address(), compiled.name());
+                    print_code(&compiled);
+                }
+                Err(e) => println!("Invalid module bytecode: {:?}", e),
+            }
+        }
+        TransactionPayload::WriteSet(change_set) => {
+            println!(
+                "Payload:         write set, {} write(s), {} event(s)",
+                change_set.write_set().len(),
+                change_set.events().len()
+            );
+            for (path, op) in change_set.write_set().iter() {
+                match op {
+                    WriteOp::Value(value) => {
+                        println!("  {} = {} bytes", path, value.len());
+                        println!("    {}", hex::encode(value));
+                    }
+                    WriteOp::Deletion => println!("  {} deleted", path),
+                }
+            }
+        }
+        TransactionPayload::Program => println!("Payload:         program"),
+    }
+}
+
+/// Disassemble the functions of a module, scripts are in their module form.
+fn print_code(module: &CompiledModule) {
+    for fdef in module.function_defs() {
+        let handle = module.function_handle_at(fdef.function);
+        println!("  fun {}", module.identifier_at(handle.name));
+        for (offset, instruction) in fdef.code.code.iter().enumerate() {
+            println!("    {:>4}: {:?}", offset, instruction);
+        }
+    }
+}
+
+fn fail(message: String) -> ! {
+    emit(&Diagnostic::error(message));
+    std::process::exit(1)
+}
@@ -3,7 +3,7 @@
 
 // replace with the official when it becomes public.
 
-use std::iter::Peekable;
+use std::{collections::BTreeMap, iter::Peekable};
 
 use functional_tests::errors::*;
 use libra_types::account_address::AccountAddress;
@@ -138,14 +138,19 @@ fn tokenize(mut s: &str) -> Result<Vec<Token>> {
     Ok(v)
 }
 
-struct Parser<I: Iterator<Item=Token>> {
+struct Parser<'a, I: Iterator<Item=Token>> {
     it: Peekable<I>,
+    addresses: &'a BTreeMap<String, AccountAddress>,
 }
 
-impl<I: Iterator<Item=Token>> Parser<I> {
-    fn new<T: IntoIterator<Item=Token, IntoIter=I>>(v: T) -> Self {
+impl<'a, I: Iterator<Item=Token>> Parser<'a, I> {
+    fn new<T: IntoIterator<Item=Token, IntoIter=I>>(
+        v: T,
+        addresses: &'a BTreeMap<String, AccountAddress>,
+    ) -> Self {
         Self {
             it: v.into_iter().peekable(),
+            addresses,
         }
     }
 
@@ -208,51 +213,98 @@ impl<I: Iterator<Item=Token>> Parser<I> {
                 TypeTag::Vector(Box::new(ty))
             }
             Token::Address(addr) => {
-                self.consume(Token::ColonColon)?;
-                match self.next()? {
-                    Token::Name(module) => {
-                        self.consume(Token::ColonColon)?;
-                        match self.next()? {
-                            Token::Name(name) => {
-                                let ty_args = if self.peek() == Some(&Token::Lt) {
-                                    self.next()?;
-                                    let ty_args = self.parse_comma_list(
-                                        |parser| parser.parse_type_tag(),
-                                        Token::Gt,
-                                        true,
-                                    )?;
-                                    self.consume(Token::Gt)?;
-                                    ty_args
-                                } else {
-                                    vec![]
-                                };
-                                TypeTag::Struct(StructTag {
-                                    address: AccountAddress::from_hex_literal(&addr)?,
-                                    module: Identifier::new(module)?,
-                                    name: Identifier::new(name)?,
-                                    type_params: ty_args,
-                                })
-                            }
-                            t => bail!("expected name, got {:?}", t),
-                        }
-                    }
+                let address = AccountAddress::from_hex_literal(&addr)?;
+                self.parse_struct_tag(address)?
+            }
+            Token::LBraceBrace => {
+                let name = match self.next()? {
+                    Token::Name(name) => name,
                     t => bail!("expected name, got {:?}", t),
+                };
+                self.consume(Token::RBraceBrace)?;
+                match self.addresses.get(&name) {
+                    Some(address) => self.parse_struct_tag(*address)?,
+                    None => bail!("unknown address {{{{{}}}}}", name),
                 }
             }
             tok => bail!("unexpected token {:?}, expected type tag", tok),
         })
     }
+
+    /// The `::Module::Name<...>` part of a struct type, after its address.
+    fn parse_struct_tag(&mut self, address: AccountAddress) -> Result<TypeTag> {
+        self.consume(Token::ColonColon)?;
+        let module = match self.next()? {
+            Token::Name(module) => module,
+            t => bail!("expected name, got {:?}", t),
+        };
+        self.consume(Token::ColonColon)?;
+        let name = match self.next()? {
+            Token::Name(name) => name,
+            t => bail!("expected name, got {:?}", t),
+        };
+        let ty_args = if self.peek() == Some(&Token::Lt) {
+            self.next()?;
+            let ty_args =
+                self.parse_comma_list(|parser| parser.parse_type_tag(), Token::Gt, true)?;
+            self.consume(Token::Gt)?;
+            ty_args
+        } else {
+            vec![]
+        };
+        Ok(TypeTag::Struct(StructTag {
+            address,
+            module: Identifier::new(module)?,
+            name: Identifier::new(name)?,
+            type_params: ty_args,
+        }))
+    }
 }
 
-pub fn parse_type_tags(s: &str) -> Result<Vec<TypeTag>> {
+/// Parse comma separated type tags, `{{name}}` addresses are looked up in `addresses`.
+pub fn parse_type_tags(
+    s: &str,
+    addresses: &BTreeMap<String, AccountAddress>,
+) -> Result<Vec<TypeTag>> {
     let mut tokens: Vec<_> = tokenize(s)?
         .into_iter()
         .filter(|tok| !tok.is_whitespace())
         .collect();
     tokens.push(Token::EOF);
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens, addresses);
     let tags = parser.parse_comma_list(|parser| parser.parse_type_tag(), Token::EOF, true);
     let tags = tags?;
     parser.consume(Token::EOF)?;
     Ok(tags)
 }
+
+#[test]
+fn test_parse_type_tags_placeholder() {
+    let alice = AccountAddress::from_hex_literal("0xa11ce").unwrap();
+    let mut addresses = BTreeMap::new();
+    addresses.insert("alice".to_string(), alice);
+    let coin = |type_params| {
+        TypeTag::Struct(StructTag {
+            address: alice,
+            module: Identifier::new("Coin").unwrap(),
+            name: Identifier::new("T").unwrap(),
+            type_params,
+        })
+    };
+
+    assert_eq!(
+        parse_type_tags("{{alice}}::Coin::T", &addresses).unwrap(),
+        vec![coin(vec![])]
+    );
+    assert_eq!(
+        parse_type_tags("vector<{{alice}}::Coin::T>, {{alice}}::Coin::T<u64>", &addresses)
+            .unwrap(),
+        vec![TypeTag::Vector(Box::new(coin(vec![]))), coin(vec![TypeTag::U64])]
+    );
+    assert_eq!(
+        parse_type_tags("0xa11ce::Coin::T", &addresses).unwrap(),
+        vec![coin(vec![])]
+    );
+    assert!(parse_type_tags("{{bob}}::Coin::T", &addresses).is_err());
+    assert!(parse_type_tags("{{alice}}", &addresses).is_err());
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    time::Duration,
+};
+
+use notify::{DebouncedEvent, RecursiveMode, Watcher, watcher};
+
+use crate::commands::source_files;
+use crate::outline::outline;
+use crate::println_color;
+use crate::runner::is_source;
+
+/// Call `on_change` with the sources changed under `dirs`, until interrupted.
+pub fn watch<F: FnMut(&[PathBuf])>(dirs: &[PathBuf], mut on_change: F) {
+    let (tx, rx) = channel();
+    let mut watcher =
+        watcher(tx, Duration::from_millis(300)).expect("Failed to start watching sources");
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .expect("Failed to watch source directory");
+    }
+
+    loop {
+        println_color("Watching");
+        print_status!("for changes, press Ctrl-C to stop\n");
+
+        let mut changed = vec![];
+        match rx.recv() {
+            Ok(event) => collect(event, &mut changed),
+            Err(_) => return,
+        }
+        // Editors often write several files at once, handle them together.
+        while let Ok(event) = rx.recv_timeout(Duration::from_millis(100)) {
+            collect(event, &mut changed);
+        }
+        changed.sort();
+        changed.dedup();
+        if !changed.is_empty() {
+            on_change(&changed);
+        }
+    }
+}
+
+fn collect(event: DebouncedEvent, changed: &mut Vec<PathBuf>) {
+    let paths = match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path) => vec![path],
+        DebouncedEvent::Rename(from, to) => vec![from, to],
+        _ => vec![],
+    };
+    changed.extend(paths.into_iter().filter(|path| is_source(path)));
+}
+
+/// Whether any of the changed files lives under one of `dirs`.
+pub fn touches(changed: &[PathBuf], dirs: &[PathBuf]) -> bool {
+    dirs.iter().any(|dir| {
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        changed.iter().any(|path| path.starts_with(&dir))
+    })
+}
+
+/// The changed files under `dir` that still exist.
+pub fn changed_in(changed: &[PathBuf], dir: &Path) -> Vec<PathBuf> {
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    changed
+        .iter()
+        .filter(|path| path.starts_with(&dir) && path.exists())
+        .cloned()
+        .collect()
+}
+
+/// Scripts that changed or import a changed module, directly or through other modules.
+pub fn affected_scripts(
+    changed: &[PathBuf],
+    module_dirs: &[PathBuf],
+    scripts: Vec<PathBuf>,
+) -> Vec<PathBuf> {
+    let modules: Vec<_> = module_dirs
+        .iter()
+        .flat_map(|dir| source_files(dir))
+        .map(|path| outline(&fs::read_to_string(&path).unwrap_or_default()))
+        .collect();
+
+    let mut affected = HashSet::new();
+    for path in changed {
+        match fs::read_to_string(path).ok().and_then(|source| outline(&source).module) {
+            Some(module) => {
+                affected.insert(module.name);
+            }
+            // A deleted module could be used by anything.
+            None if !path.exists() => return scripts,
+            None => {}
+        }
+    }
+
+    loop {
+        let before = affected.len();
+        for module in &modules {
+            if let Some(item) = &module.module {
+                if module.imports.iter().any(|i| affected.contains(&i.module)) {
+                    affected.insert(item.name.clone());
+                }
+            }
+        }
+        if affected.len() == before {
+            break;
+        }
+    }
+
+    scripts
+        .into_iter()
+        .filter(|path| {
+            if fs::canonicalize(path).map_or(false, |path| changed.contains(&path)) {
+                return true;
+            }
+            let source = fs::read_to_string(path).unwrap_or_default();
+            outline(&source)
+                .imports
+                .iter()
+                .any(|i| affected.contains(&i.module))
+        })
+        .collect()
+}
@@ -1,4 +1,4 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use libra_config::config::{ExecutionConfig, RootPath};
 use libra_crypto::{
@@ -6,16 +6,35 @@ use libra_crypto::{
     traits::*,
 };
 use libra_crypto::hash::CryptoHash;
+use language_e2e_tests::account::{Account as KeyedAccount, AccountData};
 use libra_types::{
-    account_address::AccountAddress, account_address::from_public_key, transaction::Transaction,
+    access_path::AccessPath,
+    account_address::AccountAddress,
+    account_address::from_public_key,
+    transaction::{ChangeSet, Transaction},
+    write_set::{WriteOp, WriteSetMut},
 };
 use libra_types::transaction::{RawTransaction, SignedTransaction};
 use serde::{Deserialize, Serialize};
 use stdlib::StdLibOptions;
 use vm_genesis;
 
+use crate::diagnostics::{Diagnostic, emit};
+
 const DEFAULT_CONFIG_FILE: &str = "Move.toml";
 
+/// Top level keys of a project manifest, a virtual manifest has a `[workspace]` only.
+const PROJECT_KEYS: &[&str] = &[
+    "project_name",
+    "home",
+    "compile",
+    "tx",
+    "storage",
+    "addresses",
+    "dependencies",
+    "accounts",
+];
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     project_name: String,
@@ -24,6 +43,15 @@ pub struct Config {
     pub compile: Compile,
     pub tx: DevTransaction,
     pub storage: Storage,
+    /// Addresses substituted for `{{name}}` placeholders in sources and type arguments.
+    #[serde(default)]
+    pub addresses: BTreeMap<String, AccountAddress>,
+    /// Other Move projects whose modules are compiled before the project's own.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    /// Named accounts scripts can be run from, ie: `--sender alice`.
+    #[serde(default)]
+    pub accounts: BTreeMap<String, Account>,
 }
 
 impl Config {
@@ -35,17 +63,79 @@ impl Config {
             compile: Compile::default(),
             tx: DevTransaction::default(),
             storage: Storage::default(),
+            addresses: BTreeMap::new(),
+            dependencies: BTreeMap::new(),
+            accounts: BTreeMap::new(),
         }
     }
 
     pub fn load_config(home: PathBuf) -> Self {
         crate::println_color("Loading");
-        print!("config from {:?}\n", &home.join(DEFAULT_CONFIG_FILE));
-        let content = fs::read_to_string(&home.join(DEFAULT_CONFIG_FILE))
-            .expect("Failed to loaded config files");
-        let mut cfg: Self = toml::from_str(&content).expect("Failed to loaded Move.toml");
+        print_status!("config from {:?}\n", &home.join(DEFAULT_CONFIG_FILE));
+        Self::read(home).unwrap_or_else(|diag| exit_with(&diag))
+    }
+
+    /// Load the config of a project, a missing or invalid Move.toml is reported as a
+    /// diagnostic.
+    pub fn read(home: PathBuf) -> Result<Self, Diagnostic> {
+        let path = home.join(DEFAULT_CONFIG_FILE);
+        let content = fs::read_to_string(&path).map_err(|e| {
+            Diagnostic::error(format!("failed to load {}: {}", DEFAULT_CONFIG_FILE, e))
+                .with_file(path.clone())
+        })?;
+        let mut cfg: Self = toml::from_str(&content).map_err(|e| {
+            let mut diag = Diagnostic::error(format!("invalid {}: {}", DEFAULT_CONFIG_FILE, e))
+                .with_file(path.clone());
+            if let Some((line, col)) = e.line_col() {
+                let line_start: usize = content.split('\n').take(line).map(|l| l.len() + 1).sum();
+                let offset = (line_start + col).min(content.len());
+                diag = diag.with_span(offset..offset);
+            }
+            diag
+        })?;
         cfg.home = home; // replace home with the value of argument
-        cfg
+        cfg.check_names()?;
+        Ok(cfg)
+    }
+
+    /// Load a project together with the members of its workspace, returning the settings
+    /// the workspace is built with and its packages.
+    ///
+    /// A virtual root manifest has a `[workspace]` section only, it is built with the
+    /// settings of its first member but keeps its own home, so that the cache and the
+    /// outputs are shared in its target directory.
+    pub fn load_workspace(home: PathBuf) -> (Self, Vec<Self>) {
+        let path = home.join(DEFAULT_CONFIG_FILE);
+        let manifest = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str::<toml::Value>(&content).ok());
+        if !manifest.as_ref().map_or(false, is_virtual) {
+            // Errors of a missing or invalid manifest are reported when loading it.
+            let root = Self::load_config(home);
+            let mut packages = vec![root.clone()];
+            let members = root.workspace.members.iter();
+            packages.extend(members.map(|m| Self::load_config(root.home.join(m))));
+            return (root, packages);
+        }
+
+        let error = |message: String| Diagnostic::error(message).with_file(path.clone());
+        let members = manifest
+            .expect("Move.toml was parsed")
+            .try_into::<VirtualManifest>()
+            .map_err(|e| error(format!("invalid {}: {}", DEFAULT_CONFIG_FILE, e)))
+            .unwrap_or_else(|diag| exit_with(&diag))
+            .workspace
+            .members;
+        if members.is_empty() {
+            exit_with(&error("the workspace has no members".to_string()));
+        }
+        let packages: Vec<Self> =
+            members.iter().map(|m| Self::load_config(home.join(m))).collect();
+        let mut root = packages[0].clone();
+        root.home = home;
+        root.workspace.members.clear();
+        root.dependencies.clear();
+        (root, packages)
     }
 
     pub fn initial(&self) {
@@ -60,22 +150,68 @@ impl Config {
     }
 
     pub fn genesis(&self) {
-        let change_set = vm_genesis::generate_genesis_change_set_for_testing(StdLibOptions::Staged);
+        if let Err(diag) = self.check_accounts() {
+            exit_with(&diag);
+        }
         let mut cfg = ExecutionConfig::default();
-
-        let priv_key =
-            &Ed25519PrivateKey::from_encoded_string(&self.tx.keypair_private_key).unwrap();
-        let raw_txs =
-            RawTransaction::new_change_set(self.address(), self.tx.sequence_number, change_set);
-        let signature = priv_key.sign_message(&raw_txs.hash());
-        let signed_tx =
-            SignedTransaction::new(raw_txs, self.tx.keypair_public_key.clone(), signature);
+        let raw_txs = RawTransaction::new_change_set(
+            self.address(),
+            self.tx.sequence_number,
+            self.genesis_change_set(),
+        );
+        let signed_tx = self.sign(raw_txs).unwrap_or_else(|diag| exit_with(&diag));
 
         cfg.genesis = Some(Transaction::UserTransaction(signed_tx));
         cfg.save(&RootPath::new(&self.home))
             .expect("genesis.blob was not created");
     }
 
+    /// The change set of the stdlib genesis, with the project accounts created on top of the
+    /// stdlib ones.
+    fn genesis_change_set(&self) -> ChangeSet {
+        let change_set =
+            vm_genesis::generate_genesis_change_set_for_testing(self.stdlib().options());
+        let mut writes: Vec<(AccessPath, WriteOp)> =
+            change_set.write_set().iter().cloned().collect();
+        for (_, account) in self.genesis_accounts() {
+            writes.extend(account.data().to_writeset().iter().cloned());
+        }
+        let write_set = WriteSetMut::new(writes).freeze().expect("Invalid genesis write set");
+        ChangeSet::new(write_set, change_set.events().to_vec())
+    }
+
+    /// Accounts created by the genesis, the transaction account first.
+    pub fn genesis_accounts(&self) -> Vec<(String, Account)> {
+        let tx_account = Account {
+            address: self.tx.address,
+            keypair_private_key: self.tx.keypair_private_key.clone(),
+            keypair_public_key: self.tx.keypair_public_key.clone(),
+            sequence_number: self.tx.sequence_number,
+            balance: self.tx.balance,
+        };
+        let mut accounts = vec![("tx".to_string(), tx_account)];
+        accounts.extend(self.accounts.clone());
+        accounts
+    }
+
+    /// Sign a transaction with the project keypair, which has to be the keypair of
+    /// `tx.address` for the transaction to be accepted.
+    pub fn sign(&self, raw_txn: RawTransaction) -> Result<SignedTransaction, Diagnostic> {
+        let error = |message: &str| {
+            Diagnostic::error(message).with_file(self.home.join(DEFAULT_CONFIG_FILE))
+        };
+        let priv_key = Ed25519PrivateKey::from_encoded_string(&self.tx.keypair_private_key)
+            .map_err(|_| error("tx.keypair_private_key is invalid"))?;
+        if Ed25519PublicKey::from(&priv_key) != self.tx.keypair_public_key {
+            return Err(error("tx.keypair_public_key is not the key of tx.keypair_private_key"));
+        }
+        if from_public_key(&self.tx.keypair_public_key) != self.tx.address {
+            return Err(error("tx.address is not derived from tx.keypair_public_key"));
+        }
+        let signature = priv_key.sign_message(&raw_txn.hash());
+        Ok(SignedTransaction::new(raw_txn, self.tx.keypair_public_key.clone(), signature))
+    }
+
     pub fn module_dir(&self) -> PathBuf {
         self.home.join(&self.workspace.module_dir)
     }
@@ -92,9 +228,90 @@ impl Config {
         self.home.join(&self.workspace.target_dir)
     }
 
+    pub fn custom_stdlib_dir(&self) -> PathBuf {
+        self.home.join(&self.compile.custom_stdlib_path)
+    }
+
     pub fn address(&self) -> AccountAddress {
         self.tx.address
     }
+
+    /// Named addresses and accounts. `tx` is the transaction address, and so is `sender`
+    /// unless a source is compiled for another sender.
+    pub fn addresses(&self) -> BTreeMap<String, AccountAddress> {
+        let mut addresses = self.addresses.clone();
+        for (alias, account) in &self.accounts {
+            addresses.insert(alias.clone(), account.address);
+        }
+        addresses.insert("tx".to_string(), self.address());
+        addresses.insert("sender".to_string(), self.address());
+        addresses
+    }
+
+    /// Address scripts are sent from, the transaction address or a named account.
+    pub fn sender(&self, alias: Option<&str>) -> Result<AccountAddress, Diagnostic> {
+        match alias {
+            Some(alias) => self
+                .accounts
+                .get(alias)
+                .map(|account| account.address)
+                .ok_or_else(|| {
+                    Diagnostic::error(format!("no account named {}", alias))
+                        .with_file(self.home.join(DEFAULT_CONFIG_FILE))
+                        .with_note("accounts are declared in the [accounts] section of Move.toml")
+                }),
+            None => Ok(self.address()),
+        }
+    }
+
+    /// Check that the accounts created by the genesis have valid keypairs and that no two
+    /// of them share an address, the transaction account included.
+    fn check_accounts(&self) -> Result<(), Diagnostic> {
+        let error = |message: String| {
+            Err(Diagnostic::error(message).with_file(self.home.join(DEFAULT_CONFIG_FILE)))
+        };
+        let mut aliases: BTreeMap<AccountAddress, String> = BTreeMap::new();
+        for (alias, account) in self.genesis_accounts() {
+            if let Err(message) = account.check() {
+                return error(format!("account {}: {}", alias, message));
+            }
+            if let Some(other) = aliases.insert(account.address, alias.clone()) {
+                return error(format!(
+                    "accounts {} and {} have the same address 0x{:x}",
+                    other, alias, account.address
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every name refers to a single address, named addresses and accounts share
+    /// their names and `tx` and `sender` are reserved.
+    fn check_names(&self) -> Result<(), Diagnostic> {
+        let error = |message: String| {
+            Err(Diagnostic::error(message).with_file(self.home.join(DEFAULT_CONFIG_FILE)))
+        };
+        for name in self.addresses.keys().chain(self.accounts.keys()) {
+            if name == "tx" || name == "sender" {
+                return error(format!("`{}` is reserved and can not be declared", name));
+            }
+        }
+        for alias in self.accounts.keys() {
+            if self.addresses.contains_key(alias) {
+                return error(format!("{} is declared in both [addresses] and [accounts]", alias));
+            }
+        }
+        Ok(())
+    }
+
+    /// The stdlib flavor in use, `custom_stdlib = true` is kept as a shorthand for `custom`.
+    pub fn stdlib(&self) -> StdlibFlavor {
+        if self.compile.custom_stdlib {
+            StdlibFlavor::Custom
+        } else {
+            self.compile.stdlib
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -104,6 +321,21 @@ pub struct Workspace {
     pub module_dir: PathBuf,
     pub target_dir: PathBuf,
     pub test_dir: PathBuf,
+    /// Homes of the projects built together with this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<PathBuf>,
+}
+
+/// A root manifest which is not a project itself, it only lists workspace members.
+#[derive(Deserialize)]
+struct VirtualManifest {
+    workspace: VirtualWorkspace,
+}
+
+#[derive(Deserialize)]
+struct VirtualWorkspace {
+    #[serde(default)]
+    members: Vec<PathBuf>,
 }
 
 impl Default for Workspace {
@@ -113,6 +345,7 @@ impl Default for Workspace {
             module_dir: PathBuf::from("src/modules"),
             target_dir: PathBuf::from("target"),
             test_dir: PathBuf::from("test"),
+            members: vec![],
         }
     }
 }
@@ -122,7 +355,12 @@ pub struct Compile {
     /// Script path
     pub output_source_map: bool,
     pub output_move_bytecode: bool,
+    /// Stdlib used for compiling, running and generating the genesis.
+    #[serde(default)]
+    pub stdlib: StdlibFlavor,
+    /// Compile without any stdlib, ignored with a custom stdlib.
     pub skip_stdlib: bool,
+    /// Same as `stdlib = "custom"`.
     pub custom_stdlib: bool,
     pub custom_stdlib_path: PathBuf,
 }
@@ -132,6 +370,7 @@ impl Default for Compile {
         Compile {
             output_source_map: true,
             output_move_bytecode: true,
+            stdlib: StdlibFlavor::default(),
             skip_stdlib: false,
             custom_stdlib: false,
             custom_stdlib_path: PathBuf::from("src/stdlib"),
@@ -139,12 +378,43 @@ impl Default for Compile {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StdlibFlavor {
+    /// The stdlib bytecode checked in with Libra.
+    Staged,
+    /// The stdlib compiled from the Libra sources.
+    Fresh,
+    /// The Move IR modules in `custom_stdlib_path`.
+    Custom,
+}
+
+impl StdlibFlavor {
+    /// Options of the stdlib the genesis is generated from. A custom stdlib is published
+    /// over the staged one when the genesis is loaded.
+    pub fn options(self) -> StdLibOptions {
+        match self {
+            StdlibFlavor::Fresh => StdLibOptions::Fresh,
+            StdlibFlavor::Staged | StdlibFlavor::Custom => StdLibOptions::Staged,
+        }
+    }
+}
+
+impl Default for StdlibFlavor {
+    fn default() -> Self {
+        StdlibFlavor::Staged
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DevTransaction {
     pub address: AccountAddress,
     pub keypair_private_key: String,
     pub keypair_public_key: Ed25519PublicKey,
     pub sequence_number: u64,
+    /// Balance the account starts with, in microlibra.
+    #[serde(default = "default_balance")]
+    pub balance: u64,
 }
 
 impl Default for DevTransaction {
@@ -153,12 +423,77 @@ impl Default for DevTransaction {
         Self {
             address: from_public_key(&keypair_public_key),
             sequence_number: 0,
+            balance: default_balance(),
             keypair_private_key: private_key.to_encoded_string().unwrap(),
             keypair_public_key,
         }
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub address: AccountAddress,
+    pub keypair_private_key: String,
+    pub keypair_public_key: Ed25519PublicKey,
+    #[serde(default)]
+    pub sequence_number: u64,
+    /// Balance the account starts with, in microlibra.
+    #[serde(default = "default_balance")]
+    pub balance: u64,
+}
+
+impl Account {
+    /// Check that the keypair is valid and is the one of the address.
+    fn check(&self) -> Result<(), String> {
+        let private_key = Ed25519PrivateKey::from_encoded_string(&self.keypair_private_key)
+            .map_err(|_| "keypair_private_key is invalid".to_string())?;
+        if Ed25519PublicKey::from(&private_key) != self.keypair_public_key {
+            return Err("keypair_public_key is not the key of keypair_private_key".to_string());
+        }
+        if from_public_key(&self.keypair_public_key) != self.address {
+            return Err("address is not derived from keypair_public_key".to_string());
+        }
+        Ok(())
+    }
+
+    /// Account resources of the account, as created by the genesis. The keypair is checked
+    /// before the genesis is generated.
+    fn data(&self) -> AccountData {
+        let private_key = Ed25519PrivateKey::from_encoded_string(&self.keypair_private_key)
+            .expect("keypair_private_key is invalid");
+        let account = KeyedAccount::with_keypair(private_key, self.keypair_public_key.clone());
+        AccountData::with_account(account, self.balance, self.sequence_number)
+    }
+}
+
+/// Whether a manifest only declares the members of a workspace.
+fn is_virtual(manifest: &toml::Value) -> bool {
+    manifest.get("workspace").is_some()
+        && PROJECT_KEYS.iter().all(|key| manifest.get(key).is_none())
+}
+
+/// Report a Move.toml which can not be loaded and stop.
+fn exit_with(diag: &Diagnostic) -> ! {
+    emit(diag);
+    std::process::exit(1)
+}
+
+fn default_balance() -> u64 {
+    1_000_000_000
+}
+
+/// A Move project on the local file system, ie: `shared = { path = "../shared" }`, or
+/// precompiled modules, ie: `token = { bytecode = "deps/token" }`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dependency {
+    /// Project home, relative to the home of the depending project.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// A `.mv` module file or a directory of them, relative to the project home.
+    #[serde(default)]
+    pub bytecode: Option<PathBuf>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Storage {
     pub load_state_from_genesis: bool,
@@ -181,3 +516,84 @@ fn test_generate_keypair() {
         public_key
     );
 }
+
+#[test]
+fn test_sign() {
+    let mut cfg = Config::new("sign".to_string(), PathBuf::from("sign"));
+    let raw_txn = RawTransaction::new_script(
+        cfg.address(),
+        0,
+        libra_types::transaction::Script::new(vec![], vec![], vec![]),
+        1_000_000,
+        0,
+        std::time::Duration::from_secs(0),
+    );
+    let bytes = lcs::to_bytes(&cfg.sign(raw_txn.clone()).unwrap()).unwrap();
+    let signed_txn: SignedTransaction = lcs::from_bytes(&bytes).unwrap();
+    assert!(signed_txn.check_signature().is_ok());
+    assert_eq!(signed_txn.sender(), cfg.address());
+    assert_eq!(signed_txn.public_key(), cfg.tx.keypair_public_key);
+
+    cfg.tx.address = AccountAddress::default();
+    assert!(cfg.sign(raw_txn).is_err());
+}
+
+#[test]
+fn test_check_names() {
+    let mut cfg = Config::new("names".to_string(), PathBuf::from("names"));
+    let account = cfg.genesis_accounts()[0].1.clone();
+    cfg.accounts.insert("alice".to_string(), account.clone());
+    assert!(cfg.check_names().is_ok());
+    assert_eq!(cfg.addresses()["alice"], account.address);
+    assert!(cfg.sender(Some("bob")).is_err());
+
+    cfg.addresses.insert("alice".to_string(), AccountAddress::default());
+    assert!(cfg.check_names().is_err());
+    cfg.addresses.clear();
+    cfg.accounts.insert("tx".to_string(), account);
+    assert!(cfg.check_names().is_err());
+}
+
+#[test]
+fn test_genesis_accounts() {
+    let mut cfg = Config::new("genesis".to_string(), PathBuf::from("genesis"));
+    let (private_key, public_key) = generate_keypair();
+    let alice = Account {
+        address: from_public_key(&public_key),
+        keypair_private_key: private_key.to_encoded_string().unwrap(),
+        keypair_public_key: public_key,
+        sequence_number: 0,
+        balance: 42,
+    };
+    cfg.accounts.insert("alice".to_string(), alice.clone());
+    assert!(cfg.check_accounts().is_ok());
+
+    let change_set = cfg.genesis_change_set();
+    let writes: Vec<_> = change_set.write_set().iter().cloned().collect();
+    for (_, account) in cfg.genesis_accounts() {
+        for write in account.data().to_writeset().iter() {
+            assert!(writes.contains(write));
+        }
+    }
+    // The balance is part of the writes, another balance is not found.
+    let mut rich_alice = alice.clone();
+    rich_alice.balance += 1;
+    let rich_writes = rich_alice.data().to_writeset();
+    assert!(rich_writes.iter().any(|write| !writes.contains(write)));
+
+    cfg.accounts.insert("bob".to_string(), alice);
+    assert!(cfg.check_accounts().is_err());
+    let tx_account = cfg.genesis_accounts()[0].1.clone();
+    cfg.accounts.insert("bob".to_string(), tx_account);
+    assert!(cfg.check_accounts().is_err());
+}
+
+#[test]
+fn test_is_virtual() {
+    let manifest = |content: &str| toml::from_str::<toml::Value>(content).unwrap();
+    assert!(is_virtual(&manifest("[workspace]\nmembers = ['a', 'b']\n")));
+    // A project with a broken `[tx]` is still a project, its errors are reported as such.
+    assert!(!is_virtual(&manifest("[workspace]\nmembers = ['a']\n[tx]\nbalance = 'x'\n")));
+    assert!(!is_virtual(&manifest("project_name = 'p'\n[workspace]\n")));
+    assert!(!is_virtual(&manifest("project_name = 'p'\n")));
+}
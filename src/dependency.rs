@@ -0,0 +1,205 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::PathBuf,
+};
+
+use crate::outline::outline;
+
+/// A module source and the names of the modules it imports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleSource {
+    pub path: PathBuf,
+    pub name: String,
+    pub imports: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DependencyError {
+    /// Modules importing each other, the first module is repeated at the end.
+    Cycle(Vec<ModuleSource>),
+    Duplicate(String, PathBuf, PathBuf),
+}
+
+impl fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencyError::Cycle(modules) => {
+                let names: Vec<&str> = modules.iter().map(|m| &m.name[..]).collect();
+                write!(f, "cyclic module dependency: {}", names.join(" -> "))
+            }
+            DependencyError::Duplicate(name, a, b) => write!(
+                f,
+                "module {} is declared in both {} and {}",
+                name,
+                a.display(),
+                b.display()
+            ),
+        }
+    }
+}
+
+/// Read the module name and imports of every module source.
+pub fn module_sources(paths: Vec<PathBuf>) -> Vec<ModuleSource> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let outline = outline(&fs::read_to_string(&path).unwrap_or_default());
+            ModuleSource {
+                // Keep sources with syntax errors apart, the compiler reports them.
+                name: outline
+                    .module
+                    .map(|m| m.name)
+                    .unwrap_or_else(|| path.display().to_string()),
+                imports: outline.imports.into_iter().map(|i| i.module).collect(),
+                path,
+            }
+        })
+        .collect()
+}
+
+/// Sort modules so that every module comes after the modules it imports.
+///
+/// Imports are matched by module name, imports of modules that are not part of `modules`
+/// (ie: the stdlib) are ignored. Modules without dependencies between each other keep
+/// their original order.
+pub fn sort_modules(modules: Vec<ModuleSource>) -> Result<Vec<ModuleSource>, DependencyError> {
+    let mut by_name: HashMap<&str, usize> = HashMap::new();
+    for (i, module) in modules.iter().enumerate() {
+        if let Some(&j) = by_name.get(&module.name[..]) {
+            return Err(DependencyError::Duplicate(
+                module.name.clone(),
+                modules[j].path.clone(),
+                module.path.clone(),
+            ));
+        }
+        by_name.insert(&module.name, i);
+    }
+
+    let mut order = vec![];
+    let mut done = HashSet::new();
+    for i in 0..modules.len() {
+        let mut stack = vec![];
+        visit(i, &modules, &by_name, &mut done, &mut stack, &mut order)?;
+    }
+    Ok(order.into_iter().map(|i| modules[i].clone()).collect())
+}
+
+fn visit(
+    i: usize,
+    modules: &[ModuleSource],
+    by_name: &HashMap<&str, usize>,
+    done: &mut HashSet<usize>,
+    stack: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), DependencyError> {
+    if done.contains(&i) {
+        return Ok(());
+    }
+    if let Some(pos) = stack.iter().position(|&j| j == i) {
+        let mut cycle: Vec<_> = stack[pos..].iter().map(|&j| modules[j].clone()).collect();
+        cycle.push(modules[i].clone());
+        return Err(DependencyError::Cycle(cycle));
+    }
+
+    stack.push(i);
+    for import in &modules[i].imports {
+        if let Some(&j) = by_name.get(&import[..]) {
+            visit(j, modules, by_name, done, stack, order)?;
+        }
+    }
+    stack.pop();
+
+    done.insert(i);
+    order.push(i);
+    Ok(())
+}
+
+/// Split sorted modules into layers, every module only imports modules of earlier layers
+/// so the modules of a layer can be compiled independently of each other.
+pub fn layers(sorted: Vec<ModuleSource>) -> Vec<Vec<ModuleSource>> {
+    let mut depths: HashMap<String, usize> = HashMap::new();
+    let mut layers: Vec<Vec<ModuleSource>> = vec![];
+    for module in sorted {
+        let depth = module
+            .imports
+            .iter()
+            .filter_map(|import| depths.get(import))
+            .map(|depth| depth + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(module.name.clone(), depth);
+        if layers.len() <= depth {
+            layers.resize(depth + 1, vec![]);
+        }
+        layers[depth].push(module);
+    }
+    layers
+}
+
+/// The modules `imports` need, directly or indirectly, from an already sorted list.
+pub fn required_modules(sorted: &[ModuleSource], imports: &[String]) -> Vec<ModuleSource> {
+    let mut required: HashSet<&str> = imports.iter().map(|i| &i[..]).collect();
+    // Dependencies come first in `sorted`, so walk it backwards.
+    for module in sorted.iter().rev() {
+        if required.contains(&module.name[..]) {
+            required.extend(module.imports.iter().map(|i| &i[..]));
+        }
+    }
+    sorted
+        .iter()
+        .filter(|module| required.contains(&module.name[..]))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+fn module(name: &str, imports: &[&str]) -> ModuleSource {
+    ModuleSource {
+        path: PathBuf::from(format!("{}.mvir", name)),
+        name: name.to_string(),
+        imports: imports.iter().map(|i| i.to_string()).collect(),
+    }
+}
+
+#[test]
+fn test_sort_modules() {
+    let sorted = sort_modules(vec![
+        module("A", &["C", "LibraAccount"]),
+        module("B", &[]),
+        module("C", &["B"]),
+    ])
+    .unwrap();
+    let names: Vec<_> = sorted.iter().map(|m| &m.name[..]).collect();
+    assert_eq!(names, vec!["B", "C", "A"]);
+    let required = required_modules(&sorted, &["C".to_string()]);
+    assert_eq!(required.len(), 2);
+
+    let sorted = sort_modules(vec![module("A", &["B"]), module("B", &[]), module("C", &[])]);
+    let layers: Vec<Vec<_>> = layers(sorted.unwrap())
+        .iter()
+        .map(|layer| layer.iter().map(|m| m.name.clone()).collect())
+        .collect();
+    assert_eq!(layers, vec![vec!["B", "C"], vec!["A"]]);
+
+    let err = sort_modules(vec![module("A", &["B"]), module("B", &["A"])]).unwrap_err();
+    assert_eq!(err.to_string(), "cyclic module dependency: A -> B -> A");
+}
+
+#[test]
+fn test_layers_placeholder_import() {
+    let dir = std::env::temp_dir().join(format!("move-layers-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let coin = dir.join("Coin.mvir");
+    let shop = dir.join("Shop.mvir");
+    fs::write(&coin, "module Coin {\n    resource T { v: u64 }\n}").unwrap();
+    fs::write(&shop, "module Shop {\n    import {{sender}}.Coin;\n}").unwrap();
+
+    let sorted = sort_modules(module_sources(vec![shop, coin])).unwrap();
+    let layers: Vec<Vec<_>> = layers(sorted)
+        .iter()
+        .map(|layer| layer.iter().map(|m| m.name.clone()).collect())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(layers, vec![vec!["Coin"], vec!["Shop"]]);
+}
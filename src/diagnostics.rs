@@ -0,0 +1,313 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde_json::json;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+static JSON_MESSAGES: AtomicBool = AtomicBool::new(false);
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// How diagnostics and artifacts are reported, selected with `--message-format`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MessageFormat {
+    Human,
+    /// One JSON object per line on stdout, everything else goes to stderr.
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!("unknown message format '{}', expected human or json", s)),
+        }
+    }
+}
+
+pub fn set_message_format(format: MessageFormat) {
+    JSON_MESSAGES.store(format == MessageFormat::Json, Ordering::Relaxed);
+    if format == MessageFormat::Json {
+        reserve_stdout();
+    }
+}
+
+/// Send all human readable output to stderr, stdout carries a protocol.
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+fn json_messages() -> bool {
+    JSON_MESSAGES.load(Ordering::Relaxed)
+}
+
+fn stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// The stream for progress output, stdout unless it is reserved for JSON messages.
+pub fn status_stream() -> StandardStream {
+    if stdout_reserved() {
+        StandardStream::stderr(ColorChoice::Always)
+    } else {
+        StandardStream::stdout(ColorChoice::Always)
+    }
+}
+
+pub fn print_status(args: fmt::Arguments) {
+    if stdout_reserved() {
+        let _ = io::stderr().write_fmt(args);
+    } else {
+        let _ = io::stdout().write_fmt(args);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Severity::Error => Color::Red,
+            Severity::Warning => Color::Yellow,
+        }
+    }
+}
+
+/// A problem found in a Move source, rendered like a rustc diagnostic.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    /// Byte range in the source file.
+    pub span: Option<Range<usize>>,
+    pub notes: Vec<String>,
+    /// Move level stack trace, innermost frame first.
+    pub trace: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            file: None,
+            span: None,
+            notes: vec![],
+            trace: vec![],
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message)
+        }
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_file(mut self, file: PathBuf) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_frame(mut self, frame: impl Into<String>) -> Self {
+        self.trace.push(frame.into());
+        self
+    }
+}
+
+/// Print a diagnostic, with a source snippet when the location is known.
+pub fn emit(diag: &Diagnostic) {
+    if json_messages() {
+        println!("{}", diag_to_json(diag));
+        return;
+    }
+
+    let mut stdout = status_stream();
+    let _ = stdout.set_color(
+        ColorSpec::new()
+            .set_fg(Some(diag.severity.color()))
+            .set_bold(true),
+    );
+    let _ = write!(&mut stdout, "{}", diag.severity.label());
+    if let Some(code) = &diag.code {
+        let _ = write!(&mut stdout, "[{}]", code);
+    }
+    let _ = stdout.set_color(ColorSpec::new().set_bold(true));
+    let _ = writeln!(&mut stdout, ": {}", diag.message);
+    let _ = stdout.reset();
+
+    if let Some(file) = &diag.file {
+        let source = fs::read_to_string(file).unwrap_or_default();
+        match &diag.span {
+            Some(span) if span.start <= source.len() => {
+                let (line, col) = line_col(&source, span.start);
+                blue(&mut stdout, "  --> ");
+                let _ = writeln!(&mut stdout, "{}:{}:{}", file.display(), line, col);
+                print_snippet(&mut stdout, &source, span.clone(), diag.severity.color());
+            }
+            _ => {
+                blue(&mut stdout, "  --> ");
+                let _ = writeln!(&mut stdout, "{}", file.display());
+            }
+        }
+    }
+
+    for note in &diag.notes {
+        blue(&mut stdout, "   = ");
+        let _ = writeln!(&mut stdout, "{}", note);
+    }
+    if !diag.trace.is_empty() {
+        let _ = writeln!(&mut stdout, "stack trace:");
+        for (i, frame) in diag.trace.iter().enumerate() {
+            let _ = writeln!(&mut stdout, "{:>6}: {}", i, frame);
+        }
+    }
+    let _ = writeln!(&mut stdout);
+}
+
+/// Report a file written by the compiler, only visible in JSON output.
+pub fn emit_artifact(kind: &str, source: &Path, output: &Path) {
+    if json_messages() {
+        println!(
+            "{}",
+            json!({
+                "reason": "artifact",
+                "kind": kind,
+                "source": source,
+                "path": output,
+            })
+        );
+    }
+}
+
+/// Report the outcome of a test case, only visible in JSON output.
+pub fn emit_test_result(source: &Path, passed: bool) {
+    if json_messages() {
+        println!(
+            "{}",
+            json!({
+                "reason": "test",
+                "source": source,
+                "result": if passed { "ok" } else { "failed" },
+            })
+        );
+    }
+}
+
+fn diag_to_json(diag: &Diagnostic) -> serde_json::Value {
+    let span = match (&diag.file, &diag.span) {
+        (Some(file), Some(span)) => {
+            let source = fs::read_to_string(file).unwrap_or_default();
+            let (line_start, column_start) = line_col(&source, span.start);
+            let (line_end, column_end) = line_col(&source, span.end);
+            json!({
+                "start": span.start,
+                "end": span.end,
+                "line_start": line_start,
+                "column_start": column_start,
+                "line_end": line_end,
+                "column_end": column_end,
+            })
+        }
+        _ => serde_json::Value::Null,
+    };
+    json!({
+        "reason": "diagnostic",
+        "severity": diag.severity.label(),
+        "code": diag.code,
+        "message": diag.message,
+        "file": diag.file,
+        "span": span,
+        "notes": diag.notes,
+        "trace": diag.trace,
+    })
+}
+
+fn blue(stdout: &mut StandardStream, content: &str) {
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Blue)).set_bold(true));
+    let _ = write!(stdout, "{}", content);
+    let _ = stdout.reset();
+}
+
+fn print_snippet(stdout: &mut StandardStream, source: &str, span: Range<usize>, color: Color) {
+    let (line, col) = line_col(source, span.start);
+    let text = source.lines().nth(line - 1).unwrap_or("");
+    // Only the first line of a multi-line span is underlined.
+    let spanned = source.get(span.start..span.end).unwrap_or("");
+    let width = spanned
+        .chars()
+        .count()
+        .min(text.chars().count().saturating_sub(col - 1))
+        .max(1);
+    let gutter = line.to_string().len();
+
+    blue(stdout, &format!("{:>w$} |\n", "", w = gutter));
+    blue(stdout, &format!("{:>w$} | ", line, w = gutter));
+    let _ = writeln!(stdout, "{}", text);
+    blue(stdout, &format!("{:>w$} | ", "", w = gutter));
+    let _ = stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true));
+    let _ = writeln!(stdout, "{}{}", " ".repeat(col - 1), "^".repeat(width));
+    let _ = stdout.reset();
+}
+
+/// Convert a byte offset into a 1-based (line, column) pair, the column counts characters.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[test]
+fn test_line_col() {
+    let source = "main() {\n    abort 42;\n}";
+    assert_eq!(line_col(source, 0), (1, 1));
+    assert_eq!(line_col(source, 13), (2, 5));
+    assert_eq!(line_col(source, source.len()), (3, 2));
+
+    // Columns count characters, offsets inside a character point at the character.
+    let source = "let é = 1;";
+    assert_eq!(line_col(source, 7), (1, 7));
+    assert_eq!(line_col(source, 5), (1, 5));
+}
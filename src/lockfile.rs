@@ -0,0 +1,126 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libra_crypto::HashValue;
+use libra_types::account_address::AccountAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::config::StdlibFlavor;
+use crate::diagnostics::Diagnostic;
+
+pub const LOCK_FILE: &str = "Move.lock";
+
+/// What a build resolved to, to make sure other machines build the same thing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub stdlib: StdlibFlavor,
+    /// Hash of the bytecode of the stdlib modules, a custom stdlib included.
+    #[serde(default)]
+    pub stdlib_hash: String,
+    /// Hash of `genesis.blob`, if the project has one.
+    pub genesis: Option<String>,
+    #[serde(default, rename = "module")]
+    pub modules: Vec<LockedModule>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LockedModule {
+    /// Source path, relative to the project home.
+    pub source: PathBuf,
+    pub name: String,
+    /// Address the module was compiled under.
+    pub address: AccountAddress,
+    /// Hash of the module bytecode.
+    pub hash: String,
+}
+
+impl Lockfile {
+    /// The lockfile of the project in `home`, `None` if there is none yet.
+    pub fn load(home: &Path) -> Result<Option<Self>, Diagnostic> {
+        let path = home.join(LOCK_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return Ok(None),
+        };
+        toml::from_str(&content).map(Some).map_err(|e| {
+            Diagnostic::error(format!("invalid {}: {}", LOCK_FILE, e))
+                .with_file(path)
+                .with_note("fix it, or remove it to record the next build")
+        })
+    }
+
+    pub fn save(&self, home: &Path) {
+        let content = toml::to_string_pretty(self).expect("Unable to serialize Move.lock");
+        fs::write(home.join(LOCK_FILE), content).expect("Failed to write Move.lock");
+    }
+
+    /// Differences between the locked build and `other`, one line each.
+    pub fn diff(&self, other: &Lockfile) -> Vec<String> {
+        let mut diffs = vec![];
+        if self.stdlib != other.stdlib {
+            diffs.push(format!("stdlib is {:?} instead of {:?}", other.stdlib, self.stdlib));
+        } else if self.stdlib_hash != other.stdlib_hash {
+            diffs.push("stdlib compiles to different bytecode".to_string());
+        }
+        if self.genesis != other.genesis {
+            diffs.push("genesis.blob changed".to_string());
+        }
+
+        let locked: BTreeMap<_, _> = self.modules.iter().map(|m| (&m.source, m)).collect();
+        let built: BTreeMap<_, _> = other.modules.iter().map(|m| (&m.source, m)).collect();
+        for (source, module) in &locked {
+            match built.get(source) {
+                None => diffs.push(format!("{} is no longer built", source.display())),
+                Some(other) if other.address != module.address => diffs.push(format!(
+                    "{} is compiled under 0x{:x} instead of 0x{:x}",
+                    source.display(),
+                    other.address,
+                    module.address
+                )),
+                Some(other) if other.hash != module.hash => {
+                    diffs.push(format!("{} compiles to different bytecode", source.display()))
+                }
+                Some(_) => {}
+            }
+        }
+        for source in built.keys().filter(|source| !locked.contains_key(*source)) {
+            diffs.push(format!("{} is not locked", source.display()));
+        }
+        diffs
+    }
+}
+
+pub fn hash(bytes: &[u8]) -> String {
+    HashValue::from_sha3_256(bytes).to_hex()
+}
+
+#[test]
+fn test_diff() {
+    let module = LockedModule {
+        source: PathBuf::from("src/modules/Coin.mvir"),
+        name: "Coin".to_string(),
+        address: AccountAddress::default(),
+        hash: hash(b"Coin"),
+    };
+    let locked = Lockfile {
+        stdlib: StdlibFlavor::Custom,
+        stdlib_hash: hash(b"stdlib"),
+        genesis: None,
+        modules: vec![module.clone()],
+    };
+    assert!(locked.diff(&locked.clone()).is_empty());
+
+    let mut built = locked.clone();
+    built.stdlib_hash = hash(b"edited stdlib");
+    built.modules[0].hash = hash(b"Coin2");
+    assert_eq!(
+        locked.diff(&built),
+        vec![
+            "stdlib compiles to different bytecode".to_string(),
+            "src/modules/Coin.mvir compiles to different bytecode".to_string(),
+        ]
+    );
+}
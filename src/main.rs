@@ -1,11 +1,36 @@
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use structopt::StructOpt;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use termcolor::{Color, ColorSpec, WriteColor};
 
+use diagnostics::MessageFormat;
+
+/// Like `print!`, but goes to stderr when JSON messages are written to stdout.
+macro_rules! print_status {
+    ($($arg:tt)*) => {
+        $crate::diagnostics::print_status(format_args!($($arg)*))
+    };
+}
+
+/// Like `println!`, but goes to stderr when JSON messages are written to stdout.
+macro_rules! println_status {
+    ($($arg:tt)*) => {{
+        print_status!($($arg)*);
+        print_status!("\n");
+    }};
+}
+
+mod addresses;
+mod cache;
 mod commands;
 mod config;
+mod dependency;
+mod diagnostics;
+mod lockfile;
+mod manifest;
+mod outline;
 mod runner;
 
 #[derive(StructOpt, Debug)]
@@ -21,11 +46,26 @@ pub enum Parameter {
         #[structopt(short, long, default_value = "move-project")]
         name: String,
     },
+    /// Generate genesis.blob again, creating the accounts of Move.toml.
+    Genesis {
+        /// Specify the home directory for new project.
+        #[structopt(short, long, default_value = ".", parse(from_os_str))]
+        home: PathBuf,
+    },
     /// Build all modules and scripts in src directory.
     Build {
         /// Specify the home directory for new project.
         #[structopt(short, long, default_value = ".", parse(from_os_str))]
         home: PathBuf,
+        /// Output format of diagnostics and artifacts: human, json
+        #[structopt(long, default_value = "human")]
+        message_format: MessageFormat,
+        /// Recompile and rerun the affected sources whenever a source file changes.
+        #[structopt(long)]
+        watch: bool,
+        /// Fail if the build does not match Move.lock instead of updating it.
+        #[structopt(long, conflicts_with = "watch")]
+        locked: bool,
     },
     /// Compile and run script
     Run {
@@ -38,11 +78,17 @@ pub enum Parameter {
         /// Type_arguments to run script, ie: U64, Address
         #[structopt(short, long)]
         type_args: Vec<String>,
-        /// Args assigned to move script.      
+        /// Args assigned to move script, `@alice` is the address of a named account.
         #[structopt(name = "args")]
         args: Vec<String>,
+        /// Run the script from a named account instead of the project address.
+        #[structopt(long)]
+        sender: Option<String>,
+        /// Output format of diagnostics and artifacts: human, json
+        #[structopt(long, default_value = "human")]
+        message_format: MessageFormat,
     },
-    /// Compile single script/module only, use 'move build' if your source code has dependency.
+    /// Compile single script/module, together with the project modules it imports.
     Compile {
         /// Specify the home directory for new project.
         #[structopt(short, long, default_value = ".", parse(from_os_str))]
@@ -53,15 +99,113 @@ pub enum Parameter {
         /// Compile as module.
         #[structopt(short)]
         module: bool,
+        /// Output format of diagnostics and artifacts: human, json
+        #[structopt(long, default_value = "human")]
+        message_format: MessageFormat,
     },
     /// Compile and run script
     Test {
         /// Specify the home directory for new project.
         #[structopt(short, long, default_value = ".", parse(from_os_str))]
         home: PathBuf,
+        /// Output format of diagnostics and artifacts: human, json
+        #[structopt(long, default_value = "human")]
+        message_format: MessageFormat,
+        /// Recompile and rerun the affected sources whenever a source file changes.
+        #[structopt(long)]
+        watch: bool,
+        /// Fail if the build does not match Move.lock instead of updating it.
+        #[structopt(long, conflicts_with = "watch")]
+        locked: bool,
+        /// Run the test cases from a named account instead of the package address.
+        #[structopt(long)]
+        sender: Option<String>,
+    },
+    /// Build the modules and write signed transactions publishing them to target/txns/package.
+    Package {
+        /// Specify the home directory for new project.
+        #[structopt(short, long, default_value = ".", parse(from_os_str))]
+        home: PathBuf,
+        #[structopt(flatten)]
+        txn: TxnOptions,
+    },
+    /// Craft and inspect signed transactions offline.
+    Tx {
+        #[structopt(subcommand)]
+        cmd: TxParameter,
+    },
+    /// Remove the build artifacts and the compilation cache.
+    Clean {
+        /// Specify the home directory for new project.
+        #[structopt(short, long, default_value = ".", parse(from_os_str))]
+        home: PathBuf,
+        /// Keep the compilation cache, only remove the artifacts.
+        #[structopt(long)]
+        keep_cache: bool,
+        /// Keep the signed transactions in target/txns.
+        #[structopt(long)]
+        keep_txns: bool,
+    },
+    /// Start a language server for editors, speaking LSP over stdio.
+    Lsp {
+        /// Specify the home directory for new project.
+        #[structopt(short, long, default_value = ".", parse(from_os_str))]
+        home: PathBuf,
     },
 }
 
+#[derive(StructOpt, Debug)]
+pub enum TxParameter {
+    /// Compile a script and write a signed transaction running it with the given args.
+    Script {
+        /// Specify the home directory for new project.
+        #[structopt(short, long, default_value = ".", parse(from_os_str))]
+        home: PathBuf,
+        /// Script source file.
+        #[structopt(parse(from_os_str))]
+        source_path: PathBuf,
+        /// Type_arguments of the script, ie: U64, Address
+        #[structopt(short, long)]
+        type_args: Vec<String>,
+        /// Args assigned to move script, `@alice` is the address of a named account.
+        #[structopt(name = "args")]
+        args: Vec<String>,
+        /// Where to write the transaction, target/txns/<script>.txn by default.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+        #[structopt(flatten)]
+        txn: TxnOptions,
+    },
+    /// Verify and print a signed transaction, a raw transaction or a genesis blob.
+    Decode {
+        /// Transaction file, or its bytes in hex.
+        input: String,
+    },
+}
+
+/// Gas and expiration of the transactions produced.
+#[derive(StructOpt, Debug)]
+pub struct TxnOptions {
+    /// Maximum number of gas units the transaction may use.
+    #[structopt(long, default_value = "1000000")]
+    pub max_gas_amount: u64,
+    /// Price of a gas unit, in microlibra.
+    #[structopt(long, default_value = "0")]
+    pub gas_unit_price: u64,
+    /// Seconds from now until the transaction expires.
+    #[structopt(long, default_value = "3600")]
+    pub expiration: u64,
+}
+
+impl TxnOptions {
+    pub fn expiration_time(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before the unix epoch")
+            + Duration::from_secs(self.expiration)
+    }
+}
+
 fn main() {
     let params = Parameter::from_args();
 
@@ -69,19 +213,34 @@ fn main() {
 }
 
 pub fn println_color(content: &'static str) {
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    let mut stdout = diagnostics::status_stream();
     let _ = stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true));
     let _ = write!(&mut stdout, "{:>12} ", content);
     let _ = stdout.reset();
 }
 
 fn execute(params: Parameter) {
+    match &params {
+        Parameter::Build { message_format, .. }
+        | Parameter::Run { message_format, .. }
+        | Parameter::Compile { message_format, .. }
+        | Parameter::Test { message_format, .. } => {
+            diagnostics::set_message_format(*message_format)
+        }
+        _ => {}
+    }
+
     let cmd: Box<dyn commands::Command> = match &params {
         Parameter::Build { .. } => commands::build_command(),
         Parameter::Run { .. } => commands::run_command(),
         Parameter::Compile { .. } => commands::compile_command(),
         Parameter::New { .. } => commands::new_command(),
+        Parameter::Genesis { .. } => commands::genesis_command(),
         Parameter::Test { .. } => commands::test_command(),
+        Parameter::Package { .. } => commands::package_command(),
+        Parameter::Tx { .. } => commands::tx_command(),
+        Parameter::Clean { .. } => commands::clean_command(),
+        Parameter::Lsp { .. } => commands::lsp_command(),
         //_ => panic!("unimplement"),
     };
     cmd.execute(params);
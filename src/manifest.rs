@@ -0,0 +1,79 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// What a build produced, for tools publishing the artifacts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Manifest {
+    /// Modules in the order they have to be published.
+    pub modules: Vec<ManifestModule>,
+    pub scripts: Vec<ManifestScript>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ManifestModule {
+    pub address: String,
+    pub name: String,
+    /// Hash and size of the module bytecode.
+    pub hash: String,
+    pub size: usize,
+    /// Bytecode file, relative to the project home. Missing when bytecode output is off.
+    pub path: Option<PathBuf>,
+    /// Source path, relative to the project home.
+    pub source: PathBuf,
+    /// Modules imported by the module, as `0x<address>::<name>`.
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ManifestScript {
+    pub hash: String,
+    pub size: usize,
+    pub path: Option<PathBuf>,
+    pub source: PathBuf,
+    pub type_parameters: usize,
+    /// Types of the `main` parameters, ie: `u64`, `address`, `vector<u8>`.
+    pub parameters: Vec<String>,
+}
+
+impl Manifest {
+    pub fn save(&self, target_dir: &Path) -> PathBuf {
+        let path = target_dir.join(MANIFEST_FILE);
+        let content = serde_json::to_vec_pretty(self).expect("Unable to serialize manifest");
+        fs::create_dir_all(target_dir).expect("Failed to create target directory");
+        fs::write(&path, content).expect("Failed to write manifest.json");
+        path
+    }
+}
+
+#[test]
+fn test_save() {
+    let dir = std::env::temp_dir().join(format!("move-manifest-save-{}", std::process::id()));
+    let manifest = Manifest {
+        modules: vec![ManifestModule {
+            address: "0x1".to_string(),
+            name: "Coin".to_string(),
+            hash: "00".to_string(),
+            size: 1,
+            path: None,
+            source: PathBuf::from("src/modules/Coin.mvir"),
+            dependencies: vec!["0x0::LibraAccount".to_string()],
+        }],
+        scripts: vec![],
+    };
+    let path = manifest.save(&dir);
+    let json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(path, dir.join(MANIFEST_FILE));
+    assert_eq!(json["modules"][0]["name"], "Coin");
+    assert_eq!(json["modules"][0]["path"], serde_json::Value::Null);
+    assert_eq!(json["modules"][0]["source"], "src/modules/Coin.mvir");
+    assert_eq!(json["modules"][0]["dependencies"][0], "0x0::LibraAccount");
+    assert_eq!(json["scripts"], serde_json::json!([]));
+}
@@ -0,0 +1,292 @@
+/// Declarations found in a Move IR or Move source, with byte offsets of their names.
+#[derive(Debug, Default, PartialEq)]
+pub struct Outline {
+    pub module: Option<Item>,
+    pub imports: Vec<Import>,
+    pub structs: Vec<Item>,
+    pub functions: Vec<Item>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub name: String,
+    /// Declaration header, ie: `public new(g: u64): Self.T`, or the fields of a struct.
+    pub detail: String,
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Import {
+    /// Address as written in the source, ie: `0x0` or `{{sender}}`.
+    pub address: String,
+    pub module: String,
+    /// Name the module is referred to in this file.
+    pub alias: String,
+    pub offset: usize,
+}
+
+/// Scan a module or script source for its declarations.
+///
+/// This is not a parser, it only looks at the top level declarations so that it keeps
+/// working on sources with syntax errors while they are being edited.
+pub fn outline(source: &str) -> Outline {
+    let text = strip_comments(source);
+    let bytes = text.as_bytes();
+    let mut outline = Outline::default();
+    let mut depth = 0;
+    let mut start: Option<usize> = None;
+    let mut skip_to = 0;
+
+    for (pos, &b) in bytes.iter().enumerate() {
+        if pos < skip_to {
+            continue;
+        }
+        if let Some(end) = placeholder_end(bytes, pos) {
+            // An address placeholder is part of a declaration, ie: `import {{sender}}.Coin;`.
+            if start.is_none() && depth <= 1 {
+                start = Some(pos);
+            }
+            skip_to = end;
+            continue;
+        }
+        match b {
+            b'{' | b';' | b'}' => {
+                if let Some(header_start) = start.take() {
+                    if depth <= 1 {
+                        let body = if b == b'{' {
+                            matching_brace(bytes, pos).map(|end| &text[pos + 1..end])
+                        } else {
+                            None
+                        };
+                        outline.add(&text[header_start..pos], header_start, body, depth);
+                    }
+                }
+                match b {
+                    b'{' => depth += 1,
+                    b'}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            b if !b.is_ascii_whitespace() && start.is_none() && depth <= 1 => start = Some(pos),
+            _ => {}
+        }
+    }
+    outline
+}
+
+impl Outline {
+    /// Find the import whose alias is `alias`.
+    pub fn import(&self, alias: &str) -> Option<&Import> {
+        self.imports.iter().find(|import| import.alias == alias)
+    }
+
+    fn add(&mut self, header: &str, offset: usize, body: Option<&str>, depth: usize) {
+        let words: Vec<&str> = header.split_whitespace().collect();
+        match words.as_slice() {
+            ["module", name, ..] if depth == 0 => {
+                self.module = Some(Item {
+                    name: name.to_string(),
+                    detail: header.trim().to_string(),
+                    offset: offset + header.find(name).unwrap_or(0),
+                });
+            }
+            ["import", path, rest @ ..] => {
+                let mut parts = path.rsplitn(2, '.');
+                let module = parts.next().unwrap_or("").to_string();
+                let address = parts.next().unwrap_or("").to_string();
+                let alias = match rest {
+                    ["as", alias, ..] => alias.to_string(),
+                    _ => module.clone(),
+                };
+                self.imports.push(Import {
+                    offset: offset + header.find(&module[..]).unwrap_or(0),
+                    address,
+                    module,
+                    alias,
+                });
+            }
+            ["use", path, rest @ ..] => {
+                // Move: `use 0x0::LibraAccount as Account;`
+                let mut parts = path.trim_end_matches("::").splitn(3, "::");
+                let address = parts.next().unwrap_or("").to_string();
+                let module = parts.next().unwrap_or("").to_string();
+                let alias = match rest {
+                    ["as", alias, ..] => alias.to_string(),
+                    _ => module.clone(),
+                };
+                self.imports.push(Import {
+                    offset: offset + header.rfind(&module[..]).unwrap_or(0),
+                    address,
+                    module,
+                    alias,
+                });
+            }
+            [kind, name, ..] if *kind == "resource" || *kind == "struct" => {
+                let name = identifier(name);
+                let fields = body
+                    .map(|body| body.split_whitespace().collect::<Vec<_>>().join(" "))
+                    .unwrap_or_default();
+                self.structs.push(Item {
+                    offset: offset + header.find(&name[..]).unwrap_or(0),
+                    detail: format!("{} {} {{ {} }}", kind, name, fields),
+                    name,
+                });
+            }
+            _ if header.contains('(') => {
+                let name = words
+                    .iter()
+                    .find(|word| !["public", "native", "fun"].contains(word))
+                    .map(|word| identifier(word))
+                    .unwrap_or_default();
+                if !name.is_empty() {
+                    self.functions.push(Item {
+                        offset: offset + header.find(&name[..]).unwrap_or(0),
+                        detail: header.split_whitespace().collect::<Vec<_>>().join(" "),
+                        name,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The leading identifier of `s`, ie: `new` for `new(g:`.
+fn identifier(s: &str) -> String {
+    s.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect()
+}
+
+fn matching_brace(bytes: &[u8], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut skip_to = 0;
+    for (pos, &b) in bytes.iter().enumerate().skip(open) {
+        if pos < skip_to {
+            continue;
+        }
+        if let Some(end) = placeholder_end(bytes, pos) {
+            skip_to = end;
+            continue;
+        }
+        match b {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(pos);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// End of the `{{name}}` address placeholder starting at `pos`, if there is one. Names are
+/// the ones `addresses::substitute` replaces.
+fn placeholder_end(bytes: &[u8], pos: usize) -> Option<usize> {
+    if !bytes[pos..].starts_with(b"{{") {
+        return None;
+    }
+    let len = bytes[pos + 2..].windows(2).position(|w| w == b"}}")?;
+    let name = std::str::from_utf8(&bytes[pos + 2..pos + 2 + len])
+        .ok()?
+        .trim();
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+        return None;
+    }
+    Some(pos + len + 4)
+}
+
+/// Blank out comments, keeping every other byte at its offset.
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                out.push(' ');
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                    out.push_str(&" ".repeat(c.len_utf8()));
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                out.push_str("  ");
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    out.push_str(&" ".repeat(c.len_utf8()));
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[test]
+fn test_outline() {
+    let source = "module HelloWorld {\n    import 0x0.LibraAccount as Account;\n    // new(a: u64)\n    resource T {g: u64}\n\n    public new(g: u64): Self.T {\n        return T{g: move(g)};\n    }\n    native public f();\n}";
+    let outline = outline(source);
+    assert_eq!(outline.module.unwrap().name, "HelloWorld");
+    assert_eq!(
+        outline.imports,
+        vec![Import {
+            address: "0x0".to_string(),
+            module: "LibraAccount".to_string(),
+            alias: "Account".to_string(),
+            offset: source.find("LibraAccount").unwrap(),
+        }]
+    );
+    assert_eq!(outline.structs[0].detail, "resource T { g: u64 }");
+    let names: Vec<_> = outline.functions.iter().map(|f| &f.name[..]).collect();
+    assert_eq!(names, vec!["new", "f"]);
+    assert_eq!(outline.functions[0].detail, "public new(g: u64): Self.T");
+    assert_eq!(outline.functions[0].offset, source.find("new(g").unwrap());
+}
+
+#[test]
+fn test_outline_move() {
+    let source = "module Coin {\n    use 0x0::LibraAccount as Account;\n    public fun mint(v: u64): u64 { v }\n}";
+    let outline = outline(source);
+    assert_eq!(outline.module.unwrap().name, "Coin");
+    assert_eq!(outline.imports[0].address, "0x0");
+    assert_eq!(outline.imports[0].module, "LibraAccount");
+    assert_eq!(outline.imports[0].alias, "Account");
+    assert_eq!(outline.functions[0].name, "mint");
+}
+
+#[test]
+fn test_outline_placeholder() {
+    let source = "module Shop {\n    import {{sender}}.Coin;\n    import {{alice}}.Token as T;\n    public buy(c: Coin.T) {\n        {{sender}}.Coin.pay(move(c));\n        return;\n    }\n}";
+    let outline = outline(source);
+    assert_eq!(outline.module.unwrap().name, "Shop");
+    assert_eq!(
+        outline.imports,
+        vec![
+            Import {
+                address: "{{sender}}".to_string(),
+                module: "Coin".to_string(),
+                alias: "Coin".to_string(),
+                offset: source.find("Coin").unwrap(),
+            },
+            Import {
+                address: "{{alice}}".to_string(),
+                module: "Token".to_string(),
+                alias: "T".to_string(),
+                offset: source.find("Token").unwrap(),
+            },
+        ]
+    );
+    let names: Vec<_> = outline.functions.iter().map(|f| &f.name[..]).collect();
+    assert_eq!(names, vec!["buy"]);
+}
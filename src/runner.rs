@@ -1,113 +1,1338 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     fs,
     io::Write,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
+use bytecode_source_map::source_map::ModuleSourceMap;
 use bytecode_verifier::verifier::{VerifiedModule, VerifiedScript};
-use compiler::Compiler;
+use glob::glob;
+use ir_to_bytecode::{
+    compiler::{compile_module, compile_script},
+    parser::{parse_module, parse_script},
+};
 use language_e2e_tests::data_store::FakeDataStore;
-use stdlib::{stdlib_modules, StdLibOptions};
+use libra_types::{
+    access_path::AccessPath,
+    account_address::AccountAddress,
+    account_config,
+    write_set::{WriteOp, WriteSet},
+    vm_error::{StatusCode, VMStatus},
+};
+use move_ir_types::location::Loc;
+use move_lang::{compiled_unit::CompiledUnit as MoveUnit, move_compile, shared::Address};
+use stdlib::{stdlib_files, stdlib_modules};
+use vm::{
+    access::ModuleAccess,
+    file_format::{
+        Bytecode, CompiledModule, CompiledScript, FunctionDefinitionIndex, SignatureToken,
+    },
+};
 
-use super::config::Config;
+use super::addresses::{substitute, Substitution};
+use super::cache::BuildCache;
+use super::config::{Config, StdlibFlavor};
+use super::dependency::{module_sources, sort_modules};
+use super::diagnostics::{emit_artifact, Diagnostic};
+use super::lockfile;
+use super::manifest::{Manifest, ManifestModule, ManifestScript};
+use super::outline::outline;
+
+pub type CompileResult<T> = std::result::Result<T, Vec<Diagnostic>>;
 
 pub struct MoveRunner {
     cfg: Config,
+    /// The stdlib in use followed by the modules compiled so far.
     stdlib: Vec<VerifiedModule>,
+    stdlib_len: usize,
+    stdlib_hash: String,
+    cache: BuildCache,
+    /// Artifact paths written so far and the sources they were compiled from.
+    outputs: HashMap<PathBuf, PathBuf>,
+    /// Source directories of dependencies and workspace members, and where their artifacts
+    /// go under the target.
+    source_roots: Vec<(PathBuf, PathBuf)>,
+    /// Source directories of dependencies and workspace members, with the named addresses
+    /// of their own Move.toml.
+    package_addresses: Vec<(PathBuf, BTreeMap<String, AccountAddress>)>,
     pub datastore: FakeDataStore,
+    units: Vec<CompiledUnit>,
+}
+
+/// A verified module or script, ready to be written out and added to the runner.
+pub struct Compiled<T> {
+    path: PathBuf,
+    unit: T,
+    source_map: ModuleSourceMap<Loc>,
+    substitution: Substitution,
+}
+
+/// A compiled module or script and where it came from, kept for error reporting.
+#[derive(Clone)]
+struct CompiledUnit {
+    path: PathBuf,
+    /// Scripts are kept in their module form so both can be inspected the same way.
+    module: CompiledModule,
+    source_map: Option<ModuleSourceMap<Loc>>,
+    substitution: Substitution,
+    script: bool,
+    /// Serialized bytecode and the file it was written to, empty for units which did not
+    /// pass the verifier.
+    bytecode: Vec<u8>,
+    artifact: Option<PathBuf>,
+}
+
+impl CompiledUnit {
+    /// Span in the source file of a location in the source map.
+    fn span(&self, loc: &Loc) -> Range<usize> {
+        self.substitution.original(loc_range(loc))
+    }
 }
 
 impl MoveRunner {
-    pub fn new(cfg: Config) -> Self {
+    /// Create a runner with the stdlib of the project loaded. A custom stdlib which fails
+    /// to compile is returned as diagnostics.
+    pub fn new(cfg: Config) -> CompileResult<Self> {
         super::println_color("Loaded");
-        print!(" Current address: 0x{:?}\n", cfg.address());
-        MoveRunner {
+        print_status!(" Current address: 0x{:?}\n", cfg.address());
+        let mut runner = MoveRunner {
+            cache: BuildCache::new(cfg.target_dir().join("cache")),
             cfg,
-            stdlib: stdlib_modules(StdLibOptions::Staged).to_vec(),
+            stdlib: vec![],
+            stdlib_len: 0,
+            stdlib_hash: String::new(),
             datastore: FakeDataStore::default(),
+            units: vec![],
+            outputs: HashMap::new(),
+            source_roots: vec![],
+            package_addresses: vec![],
+        };
+
+        match runner.cfg.stdlib() {
+            StdlibFlavor::Custom => runner.load_custom_stdlib()?,
+            _ if runner.cfg.compile.skip_stdlib => {}
+            flavor => runner.stdlib = stdlib_modules(flavor.options()).to_vec(),
+        }
+        runner.stdlib_len = runner.stdlib.len();
+
+        let stdlib_bytes: Vec<Vec<u8>> = runner
+            .stdlib
+            .iter()
+            .map(|m| module_bytes(m.as_inner()))
+            .collect();
+        runner.stdlib_hash =
+            BuildCache::key(&stdlib_bytes.iter().map(|b| &b[..]).collect::<Vec<_>>());
+        Ok(runner)
+    }
+
+    /// Compile the modules in `custom_stdlib_path` under the core code address, in place
+    /// of the stdlib shipped with the runner.
+    fn load_custom_stdlib(&mut self) -> CompileResult<()> {
+        let dir = self.cfg.custom_stdlib_dir();
+        super::println_color("Loading");
+        print_status!("stdlib from {}\n", dir.display());
+
+        let paths = glob(&format!("{}/**/*.mvir", dir.display()))
+            .expect("Stdlib directory is not valid.")
+            .filter_map(Result::ok)
+            .collect();
+        let modules = sort_modules(module_sources(paths)).map_err(|e| {
+            vec![Diagnostic::error(e.to_string()).with_file(dir.clone())]
+        })?;
+
+        let mut errors = vec![];
+        for module in modules {
+            match self.complie_module_as(&module.path, account_config::CORE_CODE_ADDRESS) {
+                Ok(m) => {
+                    let cm = m.as_inner();
+                    self.datastore.add_module(&cm.self_id(), cm);
+                }
+                Err(e) => errors.extend(e),
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Publish a custom stdlib again, after the data store was loaded with a genesis
+    /// containing the default one.
+    pub fn republish_stdlib(&mut self) {
+        if self.cfg.stdlib() == StdlibFlavor::Custom {
+            for m in &self.stdlib[..self.stdlib_len] {
+                let cm = m.as_inner();
+                self.datastore.add_module(&cm.self_id(), cm);
+            }
         }
     }
 
-    pub fn complie_module(&mut self, path: &Path) -> VerifiedModule {
-        let c = Compiler {
-            address: self.cfg.address(),
-            skip_stdlib_deps: false,
-            extra_deps: self.stdlib.clone(),
-            ..Compiler::default()
+    /// Stdlib modules that differ from, or are missing in, the modules of a genesis write
+    /// set. A custom stdlib is published over the genesis so it never differs.
+    pub fn stdlib_mismatches(&self, write_set: &WriteSet) -> Vec<String> {
+        if self.cfg.stdlib() == StdlibFlavor::Custom {
+            return vec![];
+        }
+        let code: HashMap<&AccessPath, &WriteOp> =
+            write_set.iter().map(|(path, op)| (path, op)).collect();
+        self.stdlib[..self.stdlib_len]
+            .iter()
+            .filter(|m| {
+                let cm = m.as_inner();
+                match code.get(&AccessPath::code_access_path(&cm.self_id())) {
+                    Some(WriteOp::Value(bytes)) => *bytes != module_bytes(cm),
+                    _ => true,
+                }
+            })
+            .map(|m| m.name().to_string())
+            .collect()
+    }
+
+    /// Load a precompiled module, it is verified and made available to the sources being
+    /// compiled like any compiled module.
+    pub fn load_module(&mut self, path: &Path) -> CompileResult<VerifiedModule> {
+        let bytes = fs::read(path).map_err(|e| {
+            vec![Diagnostic::error(format!("failed to load module file: {}", e))
+                .with_file(path.to_path_buf())]
+        })?;
+        let compiled_module = CompiledModule::deserialize(&bytes).map_err(|e| {
+            vec![Diagnostic::error(format!("invalid module bytecode: {:?}", e))
+                .with_file(path.to_path_buf())]
+        })?;
+        let unit = CompiledUnit {
+            path: path.to_path_buf(),
+            module: compiled_module.clone(),
+            source_map: None,
+            substitution: Substitution::default(),
+            script: false,
+            bytecode: bytes,
+            artifact: Some(path.to_path_buf()),
         };
+        let verified_module = VerifiedModule::new(compiled_module)
+            .map_err(|(_, errors)| verification_errors(&unit, &errors))?;
+        self.units.push(unit);
+        self.stdlib.push(verified_module.clone());
+        Ok(verified_module)
+    }
+
+    /// Hash of the bytecode of the stdlib in use.
+    pub fn stdlib_hash(&self) -> &str {
+        &self.stdlib_hash
+    }
 
-        let source = fs::read_to_string(path).expect("Failed to load source file:");
-
-        let compiled_module = c
-            .into_compiled_module(path.as_os_str().to_str().unwrap(), &source)
-            .expect("Failed to compile module");
-        if self.cfg.compile.output_move_bytecode {
-            let mut bytes: Vec<u8> = vec![];
-            compiled_module
-                .as_inner()
-                .serialize(&mut bytes)
-                .expect("Unable to serialize module");
-
-            //PathBuf::from(path.file_name().)
-            let outpath = self
-                .cfg
-                .target_dir()
-                .join(PathBuf::from(path.file_name().unwrap()))
-                .with_extension("mv");
-            write_output(&outpath, &bytes);
-        }
-
-        let verified_module = VerifiedModule::new(compiled_module).unwrap();
-        self.stdlib.push(verified_module.clone()); // add module to stdlib
-        verified_module
-    }
-
-    pub fn complie_script(&self, path: &Path) -> VerifiedScript {
-        let c = Compiler {
-            address: self.cfg.address(),
-            skip_stdlib_deps: false,
-            extra_deps: self.stdlib.clone(),
-            ..Compiler::default()
+    /// Write the artifacts of the modules of a dependency to `target/deps/<name>`.
+    pub fn add_dependency(&mut self, name: &str, cfg: &Config) {
+        let module_dir = cfg.module_dir();
+        self.source_roots.push((Path::new("deps").join(name), module_dir.clone()));
+        self.package_addresses.push((module_dir, cfg.addresses()));
+    }
+
+    /// Write the artifacts of a workspace member to `target/<member>`.
+    pub fn add_package(&mut self, cfg: &Config) {
+        if cfg.home == self.cfg.home {
+            return;
+        }
+        let prefix = match cfg.home.strip_prefix(&self.cfg.home) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => PathBuf::from(cfg.home.file_name().unwrap_or_default()),
         };
+        self.source_roots.extend(vec![
+            (prefix.join("modules"), cfg.module_dir()),
+            (prefix.join("scripts"), cfg.script_dir()),
+            (prefix.join("tests"), cfg.test_dir()),
+        ]);
+        for dir in &[cfg.module_dir(), cfg.script_dir(), cfg.test_dir()] {
+            self.package_addresses.push((dir.clone(), cfg.addresses()));
+        }
+    }
 
-        let source = fs::read_to_string(path).expect("Failed to load source file:");
+    /// Modules compiled so far, with their sources.
+    pub fn compiled_modules(&self) -> Vec<(&Path, &CompiledModule)> {
+        self.units
+            .iter()
+            .filter(|unit| !unit.script)
+            .map(|unit| (unit.path.as_path(), &unit.module))
+            .collect()
+    }
+
+    /// Modules and scripts compiled so far, paths relative to `home`. Modules are listed in
+    /// the order they were compiled, which is an order they can be published in. A custom
+    /// stdlib is part of the genesis and left out.
+    pub fn manifest(&self, home: &Path) -> Manifest {
+        let relative = |path: &Path| path.strip_prefix(home).unwrap_or(path).to_path_buf();
+        let mut manifest = Manifest::default();
+        // Sources deleted while watching are left out.
+        for unit in self.units.iter().filter(|unit| unit.path.exists()) {
+            let module = &unit.module;
+            if unit.script {
+                // The script is the `main` function of its module form.
+                let handle = module.function_handle_at(module.function_defs()[0].function);
+                manifest.scripts.push(ManifestScript {
+                    hash: lockfile::hash(&unit.bytecode),
+                    size: unit.bytecode.len(),
+                    path: unit.artifact.as_deref().map(relative),
+                    source: relative(&unit.path),
+                    type_parameters: handle.type_parameters.len(),
+                    parameters: module
+                        .signature_at(handle.parameters)
+                        .0
+                        .iter()
+                        .map(|token| type_name(module, token))
+                        .collect(),
+                });
+            } else if *module.address() != account_config::CORE_CODE_ADDRESS {
+                manifest.modules.push(ManifestModule {
+                    address: format!("0x{:x}", module.address()),
+                    name: module.name().to_string(),
+                    hash: lockfile::hash(&unit.bytecode),
+                    size: unit.bytecode.len(),
+                    path: unit.artifact.as_deref().map(relative),
+                    source: relative(&unit.path),
+                    dependencies: module
+                        .module_handles()
+                        .iter()
+                        .filter(|handle| *handle != module.self_handle())
+                        .map(|handle| {
+                            let id = module.module_id_for_handle(handle);
+                            format!("0x{:x}::{}", id.address(), id.name())
+                        })
+                        .collect(),
+                });
+            }
+        }
+        manifest
+    }
+
+    /// Take over the scripts compiled by `previous` whose sources still exist, so that a
+    /// runner started over keeps the scripts which are not compiled again.
+    pub fn keep_scripts(&mut self, previous: &MoveRunner) {
+        let scripts = previous
+            .units
+            .iter()
+            .filter(|unit| unit.script && unit.path.exists());
+        self.units.extend(scripts.cloned());
+    }
+
+    /// Modules available to the sources being compiled, the stdlib and compiled modules.
+    pub fn dependencies(&self) -> &[VerifiedModule] {
+        &self.stdlib
+    }
+
+    pub fn complie_module(&mut self, path: &Path) -> CompileResult<VerifiedModule> {
+        self.complie_module_as(path, self.cfg.address())
+    }
+
+    /// Compile a module published under `address` instead of the project address.
+    pub fn complie_module_as(
+        &mut self,
+        path: &Path,
+        address: AccountAddress,
+    ) -> CompileResult<VerifiedModule> {
+        let compiled = self.build_module(path, address)?;
+        self.add_module(compiled)
+    }
 
-        let (compiled_script, source_map) = c
-            .into_compiled_script_and_source_map(path.as_os_str().to_str().unwrap(), &source)
-            .expect("Failed to compile module");
+    /// Compile and verify a module against the modules added so far, without touching the
+    /// runner, so that independent modules can be built concurrently.
+    pub fn build_module(
+        &self,
+        path: &Path,
+        address: AccountAddress,
+    ) -> CompileResult<Compiled<VerifiedModule>> {
+        let (source, substitution) = self.load_source(path, address)?;
+        let key = self.cache_key(&source, address);
 
+        let cached = self.cache.get(&key, "mv").and_then(|bytes| {
+            // The cache is a plain directory, its entries are verified again and an
+            // entry failing the verifier is rebuilt.
+            let module = VerifiedModule::new(CompiledModule::deserialize(&bytes).ok()?).ok()?;
+            let source_map: ModuleSourceMap<Loc> =
+                serde_json::from_slice(&self.cache.get(&key, "mvsm")?).ok()?;
+            Some((module, source_map))
+        });
+        if let Some((verified_module, source_map)) = cached {
+            return Ok(Compiled {
+                path: path.to_path_buf(),
+                unit: verified_module,
+                source_map,
+                substitution,
+            });
+        }
+
+        let (compiled_module, source_map) = if is_move(path) {
+            match self.compile_move(path, address)? {
+                MoveUnit::Module {
+                    module, source_map, ..
+                } => (module, source_map),
+                MoveUnit::Script { .. } => {
+                    return Err(vec![Diagnostic::error("expected a module, found a script")
+                        .with_file(path.to_path_buf())]);
+                }
+            }
+        } else {
+            // The IR compiler only returns source maps for modules when used directly.
+            let parsed_module = parse_module(path.as_os_str().to_str().unwrap(), &source)
+                .map_err(|e| vec![compile_error(path, &e, &substitution)])?;
+            compile_module(
+                address,
+                parsed_module,
+                self.stdlib.iter().map(|m| m.as_inner()),
+            )
+            .map_err(|e| vec![compile_error(path, &e, &substitution)])?
+        };
+
+        let verified_module = VerifiedModule::new(compiled_module).map_err(|(module, errors)| {
+            let unit = CompiledUnit {
+                path: path.to_path_buf(),
+                module,
+                source_map: Some(source_map.clone()),
+                substitution: substitution.clone(),
+                script: false,
+                bytecode: vec![],
+                artifact: None,
+            };
+            verification_errors(&unit, &errors)
+        })?;
+        self.cache
+            .put(&key, "mv", &module_bytes(verified_module.as_inner()));
+        self.cache.put(
+            &key,
+            "mvsm",
+            &serde_json::to_vec(&source_map).expect("Unable to serialize source map"),
+        );
+        Ok(Compiled {
+            path: path.to_path_buf(),
+            unit: verified_module,
+            source_map,
+            substitution,
+        })
+    }
+
+    /// Write out a built module and make it available to the sources compiled next.
+    pub fn add_module(
+        &mut self,
+        compiled: Compiled<VerifiedModule>,
+    ) -> CompileResult<VerifiedModule> {
+        let Compiled {
+            path,
+            unit,
+            source_map,
+            substitution,
+        } = compiled;
+        self.write_module(&path, unit.as_inner(), source_map, substitution)?;
+        self.stdlib.push(unit.clone()); // add module to stdlib
+        Ok(unit)
+    }
+
+    fn write_module(
+        &mut self,
+        path: &Path,
+        compiled_module: &CompiledModule,
+        source_map: ModuleSourceMap<Loc>,
+        substitution: Substitution,
+    ) -> CompileResult<()> {
+        let name = compiled_module.name().to_string();
+        let outpath = self.artifact_path(path, Some(&name))?;
+        if self.cfg.compile.output_source_map {
+            let bytes = serde_json::to_vec(&source_map).expect("Unable to serialize module");
+            write_output("source-map", path, &artifact_file(&outpath, "mvsm"), &bytes);
+        }
+
+        let bytecode = module_bytes(compiled_module);
+        let artifact = if self.cfg.compile.output_move_bytecode {
+            let artifact = artifact_file(&outpath, "mv");
+            write_output("module", path, &artifact, &bytecode);
+            Some(artifact)
+        } else {
+            None
+        };
+
+        self.units.push(CompiledUnit {
+            path: path.to_path_buf(),
+            module: compiled_module.clone(),
+            source_map: Some(source_map),
+            substitution,
+            script: false,
+            bytecode,
+            artifact,
+        });
+        Ok(())
+    }
+
+    pub fn complie_script(&mut self, path: &Path) -> CompileResult<VerifiedScript> {
+        self.complie_script_as(path, self.cfg.address())
+    }
+
+    /// Compile a script sent by `address` instead of the project address.
+    pub fn complie_script_as(
+        &mut self,
+        path: &Path,
+        address: AccountAddress,
+    ) -> CompileResult<VerifiedScript> {
+        let compiled = self.build_script(path, address)?;
+        self.add_script(compiled)
+    }
+
+    /// Compile and verify a script without touching the runner, so that scripts can be
+    /// built concurrently.
+    pub fn build_script(
+        &self,
+        path: &Path,
+        address: AccountAddress,
+    ) -> CompileResult<Compiled<VerifiedScript>> {
+        let (source, substitution) = self.load_source(path, address)?;
+        let key = self.cache_key(&source, address);
+
+        let cached = self.cache.get(&key, "mv").and_then(|bytes| {
+            // The cache is a plain directory, its entries are verified again and an
+            // entry failing the verifier is rebuilt.
+            let script = VerifiedScript::new(CompiledScript::deserialize(&bytes).ok()?).ok()?;
+            let source_map: ModuleSourceMap<Loc> =
+                serde_json::from_slice(&self.cache.get(&key, "mvsm")?).ok()?;
+            Some((script, source_map))
+        });
+        if let Some((verified_script, source_map)) = cached {
+            return Ok(Compiled {
+                path: path.to_path_buf(),
+                unit: verified_script,
+                source_map,
+                substitution,
+            });
+        }
+
+        let (compiled_script, source_map) = if is_move(path) {
+            match self.compile_move(path, address)? {
+                MoveUnit::Script {
+                    script, source_map, ..
+                } => (script, source_map),
+                MoveUnit::Module { .. } => {
+                    return Err(vec![Diagnostic::error("expected a script, found a module")
+                        .with_file(path.to_path_buf())]);
+                }
+            }
+        } else {
+            let parsed_script = parse_script(path.as_os_str().to_str().unwrap(), &source)
+                .map_err(|e| vec![compile_error(path, &e, &substitution)])?;
+            // The stdlib in use, if any, is part of our dependencies.
+            compile_script(
+                address,
+                parsed_script,
+                self.stdlib.iter().map(|m| m.as_inner()),
+            )
+            .map_err(|e| vec![compile_error(path, &e, &substitution)])?
+        };
+
+        let verified_script = VerifiedScript::new(compiled_script).map_err(|(script, errors)| {
+            let unit = CompiledUnit {
+                path: path.to_path_buf(),
+                module: script.into_module(),
+                source_map: Some(source_map.clone()),
+                substitution: substitution.clone(),
+                script: true,
+                bytecode: vec![],
+                artifact: None,
+            };
+            verification_errors(&unit, &errors)
+        })?;
+        self.cache
+            .put(&key, "mv", &script_bytes(verified_script.as_inner()));
+        self.cache.put(
+            &key,
+            "mvsm",
+            &serde_json::to_vec(&source_map).expect("Unable to serialize source map"),
+        );
+        Ok(Compiled {
+            path: path.to_path_buf(),
+            unit: verified_script,
+            source_map,
+            substitution,
+        })
+    }
+
+    /// Write out a built script.
+    pub fn add_script(
+        &mut self,
+        compiled: Compiled<VerifiedScript>,
+    ) -> CompileResult<VerifiedScript> {
+        let Compiled {
+            path,
+            unit,
+            source_map,
+            substitution,
+        } = compiled;
+        self.write_script(&path, unit.as_inner(), source_map, substitution)?;
+        Ok(unit)
+    }
+
+    fn write_script(
+        &mut self,
+        path: &Path,
+        compiled_script: &CompiledScript,
+        source_map: ModuleSourceMap<Loc>,
+        substitution: Substitution,
+    ) -> CompileResult<()> {
+        let outpath = self.artifact_path(path, None)?;
         if self.cfg.compile.output_source_map {
             let bytes = serde_json::to_vec(&source_map).expect("Unable to serialize script");
+            write_output("source-map", path, &artifact_file(&outpath, "mvsm"), &bytes);
+        }
+
+        let bytecode = script_bytes(compiled_script);
+        let artifact = if self.cfg.compile.output_move_bytecode {
+            let artifact = artifact_file(&outpath, "mv");
+            write_output("script", path, &artifact, &bytecode);
+            Some(artifact)
+        } else {
+            None
+        };
+
+        self.units.retain(|unit| unit.path != path);
+        self.units.push(CompiledUnit {
+            path: path.to_path_buf(),
+            module: compiled_script.clone().into_module(),
+            source_map: Some(source_map),
+            substitution,
+            script: true,
+            bytecode,
+            artifact,
+        });
+        Ok(())
+    }
+
+    /// Read a source, with the address placeholders of Move IR sources substituted by the
+    /// named addresses of the package the source belongs to. `{{sender}}` is the address the
+    /// source is compiled under.
+    fn load_source(
+        &self,
+        path: &Path,
+        address: AccountAddress,
+    ) -> CompileResult<(String, Substitution)> {
+        let source = read_source(path)?;
+        if is_move(path) {
+            return Ok((source, Substitution::default()));
+        }
+        let mut addresses = self
+            .package_addresses
+            .iter()
+            .find(|(dir, _)| path.starts_with(dir))
+            .map_or_else(|| self.cfg.addresses(), |(_, addresses)| addresses.clone());
+        addresses.insert("sender".to_string(), address);
+        substitute(&source, &addresses).map_err(|(span, name)| {
+            vec![Diagnostic::error(format!("unknown address `{{{{{}}}}}`", name))
+                .with_file(path.to_path_buf())
+                .with_span(span)
+                .with_note("named addresses are declared in the [addresses] section of Move.toml")]
+        })
+    }
 
-            let outpath = self
-                .cfg
-                .target_dir()
-                .join(PathBuf::from(path.file_name().unwrap()))
-                .with_extension("mvsm");
-            write_output(&outpath, &bytes);
+    /// Compile a Move source file, which must contain a single module or script.
+    ///
+    /// The Move compiler works from sources only: the Move stdlib and the `.move` modules
+    /// of the project are passed as dependencies, Move IR modules can not be imported.
+    fn compile_move(&self, path: &Path, address: AccountAddress) -> CompileResult<MoveUnit> {
+        let target = path.display().to_string();
+        let deps: Vec<String> = self
+            .move_dependencies()
+            .into_iter()
+            .filter(|dep| *dep != target)
+            .collect();
+        let (_, units) = move_compile(&[target], &deps, Some(Address::new(address.into())))
+            .map_err(|e| vec![compile_error(path, &e, &Substitution::default())])?;
+        let mut units = units
+            .map_err(|errors| errors.iter().map(|error| move_error(error)).collect::<Vec<_>>())?;
+        if units.len() != 1 {
+            return Err(vec![Diagnostic::error(format!(
+                "expected exactly one module or script, found {}",
+                units.len()
+            ))
+            .with_file(path.to_path_buf())]);
         }
+        Ok(units.remove(0))
+    }
 
-        if self.cfg.compile.output_move_bytecode {
-            let mut bytes: Vec<u8> = vec![];
-            compiled_script
-                .as_inner()
-                .serialize(&mut bytes)
-                .expect("Unable to serialize script");
+    fn move_dependencies(&self) -> Vec<String> {
+        let mut deps = match self.cfg.stdlib() {
+            StdlibFlavor::Custom => vec![],
+            _ if self.cfg.compile.skip_stdlib => vec![],
+            _ => stdlib_files(),
+        };
+        let dirs = self
+            .source_roots
+            .iter()
+            .filter(|(kind, _)| kind.starts_with("deps") || kind.ends_with("modules"))
+            .map(|(_, dir)| dir.clone());
+        for dir in dirs.chain(vec![self.cfg.module_dir()]) {
+            deps.extend(
+                glob(&format!("{}/**/*.move", dir.display()))
+                    .expect("Source directory is not valid.")
+                    .filter_map(Result::ok)
+                    .map(|path| path.display().to_string()),
+            );
+        }
+        deps
+    }
 
-            let outpath = self
-                .cfg
-                .target_dir()
-                .join(PathBuf::from(path.file_name().unwrap()))
-                .with_extension("mv");
-            write_output(&outpath, &bytes);
+    /// Remove the artifacts of sources that were deleted or renamed, and return them.
+    ///
+    /// A build compiles every source but the tests, so any other artifact it did not
+    /// write is stale. Test artifacts are kept as long as their source exists.
+    pub fn prune_artifacts(&self) -> Vec<PathBuf> {
+        let mut pruned = vec![];
+        for (kind, dir) in self.artifact_roots() {
+            let out = self.cfg.target_dir().join(&kind);
+            let artifacts = glob(&format!("{}/**/*.mv*", out.display()))
+                .expect("Target directory is not valid.")
+                .filter_map(Result::ok)
+                .filter(|path| path.extension().map_or(false, |ext| ext == "mv" || ext == "mvsm"));
+            for artifact in artifacts {
+                let stem = artifact.with_extension("");
+                if self.outputs.contains_key(&stem) {
+                    continue;
+                }
+                if kind.ends_with("tests") {
+                    let source = dir.join(stem.strip_prefix(&out).unwrap_or(&stem));
+                    let exists = ["mvir", "move"]
+                        .iter()
+                        .any(|ext| artifact_file(&source, ext).exists());
+                    if exists {
+                        continue;
+                    }
+                }
+                if fs::remove_file(&artifact).is_ok() {
+                    pruned.push(artifact);
+                }
+            }
         }
+        pruned
+    }
 
-        VerifiedScript::new(compiled_script).unwrap()
+    /// Source directories and the target directories their artifacts go to.
+    fn artifact_roots(&self) -> Vec<(PathBuf, PathBuf)> {
+        let mut roots = vec![
+            (PathBuf::from("modules"), self.cfg.module_dir()),
+            (PathBuf::from("scripts"), self.cfg.script_dir()),
+            (PathBuf::from("tests"), self.cfg.test_dir()),
+            (PathBuf::from("stdlib"), self.cfg.custom_stdlib_dir()),
+        ];
+        roots.extend(self.source_roots.iter().cloned());
+        roots
     }
+
+    /// Artifact path of a source without extension, mirroring the source directories:
+    /// `target/modules/<dir>/<module name>`, `target/scripts/<path>` and `target/tests/<path>`.
+    fn artifact_path(&mut self, source: &Path, module: Option<&str>) -> CompileResult<PathBuf> {
+        let absolute = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let source_abs = absolute(source);
+        let (kind, relative) = self
+            .artifact_roots()
+            .into_iter()
+            .find_map(|(kind, dir)| {
+                let relative = source_abs.strip_prefix(absolute(&dir)).ok()?;
+                Some((kind, relative.to_path_buf()))
+            })
+            .unwrap_or_else(|| {
+                let kind = if module.is_some() { "modules" } else { "scripts" };
+                (PathBuf::from(kind), PathBuf::from(source.file_name().unwrap()))
+            });
+
+        let mut outpath = self.cfg.target_dir().join(kind).join(&relative);
+        match module {
+            Some(name) => outpath.set_file_name(name),
+            // Only the source extension is dropped, `a.b.mvir` and `a.c.mvir` keep
+            // distinct artifacts.
+            None => outpath = outpath.with_extension(""),
+        }
+
+        if let Some(other) = self.outputs.get(&outpath) {
+            if other != &source_abs {
+                return Err(vec![Diagnostic::error(format!(
+                    "{} is also produced by {}",
+                    outpath.display(),
+                    other.display()
+                ))
+                .with_file(source.to_path_buf())]);
+            }
+        }
+        self.outputs.insert(outpath.clone(), source_abs);
+        if let Some(dir) = outpath.parent() {
+            fs::create_dir_all(dir).expect("Failed to create target directory");
+        }
+        Ok(outpath)
+    }
+
+    /// Cache key of a source, covering everything the compiled output depends on: the
+    /// source itself, the sender address, the stdlib and the modules imported by the source.
+    fn cache_key(&self, source: &str, address: AccountAddress) -> String {
+        let imports: Vec<Vec<u8>> = outline(source)
+            .imports
+            .iter()
+            .filter_map(|import| {
+                // `Transaction` is the IR alias of the sender, the module is then compiled
+                // under `address` too.
+                let import_address = match import.address.as_str() {
+                    "Transaction" => Some(address),
+                    literal => AccountAddress::from_hex_literal(literal).ok(),
+                };
+                self.stdlib
+                    .iter()
+                    .rev()
+                    .find(|m| {
+                        let m = m.as_inner();
+                        m.name().as_str() == import.module
+                            && import_address.map_or(true, |a| *m.address() == a)
+                    })
+                    .map(|m| module_bytes(m.as_inner()))
+            })
+            .collect();
+
+        let mut parts: Vec<&[u8]> = vec![
+            env!("CARGO_PKG_VERSION").as_bytes(),
+            source.as_bytes(),
+            address.as_ref(),
+            self.stdlib_hash.as_bytes(),
+        ];
+        parts.extend(imports.iter().map(|bytes| &bytes[..]));
+        BuildCache::key(&parts)
+    }
+
+    /// Turn a failed execution of `script` into a diagnostic pointing at the source.
+    pub fn report_vm_error(&self, status: &VMStatus, script: &Path) -> Diagnostic {
+        let mut diag = Diagnostic::error(describe_status(status))
+            .with_code(format!("{:?}", status.major_status));
+        if let Some(sub_status) = status.sub_status {
+            diag = diag.with_note(format!("abort code: {}", sub_status));
+        }
+
+        let frames = status
+            .message
+            .as_ref()
+            .map(|msg| parse_frames(msg))
+            .unwrap_or_default();
+
+        // The innermost frame is where the error happened.
+        for (i, frame) in frames.iter().enumerate() {
+            let location = self.locate(script, frame, Some(frame.pc));
+            let mut line = format!("{}::{} at offset {}", frame.module, frame.function, frame.pc);
+            if let Some((path, span)) = location {
+                line.push_str(&format!(" ({})", display_location(&path, span.as_ref())));
+                if i == 0 {
+                    diag = diag.with_file(path);
+                    if let Some(span) = span {
+                        diag = diag.with_span(span);
+                    }
+                }
+            }
+            diag = diag.with_frame(line);
+        }
+
+        if frames.is_empty() {
+            if let (StatusCode::ABORTED, Some(code)) = (status.major_status, status.sub_status) {
+                // The VM does not report where an abort happened. An abort with a constant
+                // code compiles to `LdU64(code); Abort`, so look for that in our own code.
+                let sites = self.abort_sites(script, code);
+                if sites.len() == 1 {
+                    let (path, function, pc, span) = sites[0].clone();
+                    diag = diag
+                        .with_note(format!("raised in {} at offset {}", function, pc))
+                        .with_file(path);
+                    if let Some(span) = span {
+                        diag = diag.with_span(span);
+                    }
+                } else {
+                    for (path, function, pc, span) in sites.iter() {
+                        diag = diag.with_note(format!(
+                            "possibly raised in {} at offset {} ({})",
+                            function,
+                            pc,
+                            display_location(path, span.as_ref())
+                        ));
+                    }
+                    if sites.is_empty() {
+                        diag = diag.with_note("raised outside of the project sources");
+                    }
+                    diag = diag.with_file(script.to_path_buf());
+                }
+            } else {
+                diag = diag.with_file(script.to_path_buf());
+            }
+        }
+
+        if let Some(message) = &status.message {
+            if frames.is_empty() {
+                diag = diag.with_note(message.clone());
+            }
+        }
+        diag
+    }
+
+    /// Find the file and source span of the function of `frame` at bytecode offset `pc`.
+    ///
+    /// Every script is named `<SELF>`, so a script frame is resolved against `script`, the one
+    /// which was executed, and other frames against the modules only.
+    fn locate(
+        &self,
+        script: &Path,
+        frame: &Frame,
+        pc: Option<u16>,
+    ) -> Option<(PathBuf, Option<Range<usize>>)> {
+        let unit = if frame.module == SCRIPT_NAME {
+            self.units.iter().find(|unit| unit.script && unit.path == script)?
+        } else {
+            self.units.iter().find(|unit| {
+                !unit.script
+                    && unit_name(&unit.module) == frame.module
+                    && format!("{:x}", unit.module.address()).starts_with(&frame.address)
+            })?
+        };
+        let (idx, _) = function_defs(&unit.module)
+            .into_iter()
+            .find(|(_, name)| *name == frame.function)?;
+        let span = unit.source_map.as_ref().and_then(|map| {
+            let fdef_idx = FunctionDefinitionIndex::new(idx as u16);
+            match pc {
+                Some(pc) => map.get_code_location(fdef_idx, pc).ok(),
+                None => map
+                    .get_function_source_map(fdef_idx)
+                    .ok()
+                    .map(|f| f.decl_location),
+            }
+            .map(|loc| unit.span(&loc))
+        });
+        Some((unit.path.clone(), span))
+    }
+
+    /// Every `LdU64(code); Abort` sequence in the compiled modules and in `script`.
+    fn abort_sites(
+        &self,
+        script: &Path,
+        code: u64,
+    ) -> Vec<(PathBuf, String, u16, Option<Range<usize>>)> {
+        let mut sites = vec![];
+        for unit in self.units.iter().filter(|unit| !unit.script || unit.path == script) {
+            for (idx, name) in function_defs(&unit.module) {
+                let fdef = &unit.module.function_defs()[idx];
+                for (pc, window) in fdef.code.code.windows(2).enumerate() {
+                    if window[0] == Bytecode::LdU64(code) && window[1] == Bytecode::Abort {
+                        let pc = (pc + 1) as u16;
+                        let span = unit.source_map.as_ref().and_then(|map| {
+                            map.get_code_location(FunctionDefinitionIndex::new(idx as u16), pc)
+                                .ok()
+                                .map(|loc| unit.span(&loc))
+                        });
+                        let function = format!("{}::{}", unit_name(&unit.module), name);
+                        sites.push((unit.path.clone(), function, pc, span));
+                    }
+                }
+            }
+        }
+        sites
+    }
+}
+
+/// Map bytecode verifier errors of a unit back to its source.
+fn verification_errors(unit: &CompiledUnit, errors: &[VMStatus]) -> Vec<Diagnostic> {
+    errors
+        .iter()
+        .map(|status| {
+            let message = status.message.clone().unwrap_or_default();
+            let mut diag = Diagnostic::error(format!("bytecode verification failed: {}", message))
+                .with_code(format!("{:?}", status.major_status))
+                .with_file(unit.path.clone());
+
+            let (fdef, offset) = parse_verifier_location(&message);
+            if let Some(fdef) = fdef {
+                if let Some((_, name)) = function_defs(&unit.module).into_iter().nth(fdef) {
+                    diag = diag.with_note(format!("in function {}", name));
+                }
+                let span = unit.source_map.as_ref().and_then(|map| {
+                    let fdef_idx = FunctionDefinitionIndex::new(fdef as u16);
+                    match offset {
+                        Some(offset) => map.get_code_location(fdef_idx, offset).ok(),
+                        None => map
+                            .get_function_source_map(fdef_idx)
+                            .ok()
+                            .map(|f| f.decl_location),
+                    }
+                    .map(|loc| unit.span(&loc))
+                });
+                if let Some(span) = span {
+                    diag = diag.with_span(span);
+                }
+            }
+            diag
+        })
+        .collect()
+}
+
+/// Name of the module form of every script.
+const SCRIPT_NAME: &str = "<SELF>";
+
+/// A frame of the call stack dumped by the interpreter, ie: `frame #0: 0x00000000.M.f [pc = 3]`.
+#[derive(Debug, PartialEq)]
+struct Frame {
+    /// Hex digits of the short form of the module address, empty if it was not printed.
+    address: String,
+    module: String,
+    function: String,
+    pc: u16,
+}
+
+/// Parse the call stack from a VM error message, innermost frame first.
+fn parse_frames(message: &str) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = message
+        .lines()
+        .filter_map(|line| {
+            let rest = line.get(line.find("frame #")?..)?;
+            let name_start = rest.find(": ")? + 2;
+            let name_end = rest.find(" [pc = ")?;
+            let pc_end = rest.get(name_end..)?.find(']')? + name_end;
+            let name = rest.get(name_start..name_end)?.trim();
+            let pc = rest.get(name_end + 7..pc_end)?.trim().parse().ok()?;
+            let (module, function) = split_path(name);
+            let (address, module) = split_path(module);
+            let address = address.trim_start_matches("0x").to_lowercase();
+            Some(Frame {
+                address,
+                module: module.to_string(),
+                function: function.to_string(),
+                pc,
+            })
+        })
+        .collect();
+    frames.reverse();
+    frames
+}
+
+/// Split `a.b` or `a::b` at the last separator, `("", path)` if there is none.
+fn split_path(path: &str) -> (&str, &str) {
+    let split = path.rfind("::").map(|i| (i, 2)).or_else(|| path.rfind('.').map(|i| (i, 1)));
+    match split {
+        Some((i, n)) => (&path[..i], &path[i + n..]),
+        None => ("", path),
+    }
+}
+
+fn read_source(path: &Path) -> CompileResult<String> {
+    fs::read_to_string(path).map_err(|e| {
+        vec![Diagnostic::error(format!("failed to load source file: {}", e))
+            .with_file(path.to_path_buf())]
+    })
+}
+
+/// Whether `path` is a Move IR or Move source.
+pub fn is_source(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "mvir" || ext == "move")
+}
+
+fn is_move(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "move")
+}
+
+/// A Move compiler error, its first label is the primary one.
+fn move_error(error: &[(Loc, String)]) -> Diagnostic {
+    let (loc, message) = &error[0];
+    let mut diag = Diagnostic::error(message.clone())
+        .with_file(PathBuf::from(loc.file()))
+        .with_span(loc_range(loc));
+    for (loc, label) in &error[1..] {
+        let span = loc_range(loc);
+        diag = diag.with_note(format!(
+            "{}: {}",
+            display_location(Path::new(loc.file()), Some(&span)),
+            label
+        ));
+    }
+    diag
+}
+
+/// The IR compiler only reports locations inside its messages, ie: `Invalid token at [12, 15)`.
+fn compile_error(path: &Path, err: &anyhow::Error, substitution: &Substitution) -> Diagnostic {
+    let message = format!("{:#}", err);
+    let mut diag = Diagnostic::error(message.clone()).with_file(path.to_path_buf());
+    if let Some(span) = span_from_message(&message) {
+        diag = diag.with_span(substitution.original(span));
+    }
+    diag
 }
 
-fn write_output(path: &PathBuf, buf: &[u8]) {
+fn span_from_message(message: &str) -> Option<Range<usize>> {
+    message.match_indices('[').find_map(|(i, _)| {
+        let rest = &message[i + 1..];
+        let end = rest.find(')')?;
+        let mut bounds = rest[..end].split(',').map(|n| n.trim().parse::<usize>());
+        match (bounds.next(), bounds.next(), bounds.next()) {
+            (Some(Ok(start)), Some(Ok(end)), None) if start <= end => Some(start..end),
+            _ => None,
+        }
+    })
+}
+
+/// Extract the function definition index and code offset from a verifier message,
+/// ie: `At offset 4 at index 1 while indexing function definition`.
+fn parse_verifier_location(message: &str) -> (Option<usize>, Option<u16>) {
+    let lower = message.to_lowercase();
+    let number_after = |pattern: &str| -> Option<usize> {
+        let start = lower.find(pattern)? + pattern.len();
+        lower[start..]
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()
+    };
+    let fdef = if lower.contains("function definition") || lower.contains("functiondefinition") {
+        number_after("at index ")
+    } else {
+        None
+    };
+    let offset = number_after("at code offset ")
+        .or_else(|| number_after("at offset "))
+        .map(|offset| offset as u16);
+    (fdef, offset)
+}
+
+fn describe_status(status: &VMStatus) -> String {
+    match (status.major_status, status.sub_status) {
+        (StatusCode::ABORTED, Some(code)) => format!("script aborted with code {}", code),
+        (StatusCode::EXECUTED, _) => "script executed".to_string(),
+        (code, _) => format!("script failed with status {:?}", code),
+    }
+}
+
+fn unit_name(module: &CompiledModule) -> String {
+    module.name().to_string()
+}
+
+/// All function definitions of a module with their names.
+fn function_defs(module: &CompiledModule) -> Vec<(usize, String)> {
+    module
+        .function_defs()
+        .iter()
+        .enumerate()
+        .map(|(idx, fdef)| {
+            let handle = module.function_handle_at(fdef.function);
+            (idx, module.identifier_at(handle.name).to_string())
+        })
+        .collect()
+}
+
+/// Move name of a type in a signature of `module`.
+fn type_name(module: &CompiledModule, token: &SignatureToken) -> String {
+    match token {
+        SignatureToken::Bool => "bool".to_string(),
+        SignatureToken::U8 => "u8".to_string(),
+        SignatureToken::U64 => "u64".to_string(),
+        SignatureToken::U128 => "u128".to_string(),
+        SignatureToken::Address => "address".to_string(),
+        SignatureToken::Vector(inner) => format!("vector<{}>", type_name(module, inner)),
+        SignatureToken::Struct(idx) => {
+            let handle = module.struct_handle_at(*idx);
+            module.identifier_at(handle.name).to_string()
+        }
+        SignatureToken::Reference(inner) => format!("&{}", type_name(module, inner)),
+        SignatureToken::MutableReference(inner) => format!("&mut {}", type_name(module, inner)),
+        SignatureToken::TypeParameter(idx) => format!("T{}", idx),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn loc_range(loc: &Loc) -> Range<usize> {
+    loc.start().0 as usize..loc.end().0 as usize
+}
+
+fn display_location(path: &Path, span: Option<&Range<usize>>) -> String {
+    match span.and_then(|span| {
+        fs::read_to_string(path)
+            .ok()
+            .map(|source| super::diagnostics::line_col(&source, span.start))
+    }) {
+        Some((line, col)) => format!("{}:{}:{}", path.display(), line, col),
+        None => path.display().to_string(),
+    }
+}
+
+fn module_bytes(module: &CompiledModule) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    module
+        .as_inner()
+        .serialize(&mut bytes)
+        .expect("Unable to serialize module");
+    bytes
+}
+
+fn script_bytes(script: &CompiledScript) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    script
+        .as_inner()
+        .serialize(&mut bytes)
+        .expect("Unable to serialize script");
+    bytes
+}
+
+/// `path` with `.extension` appended to its full file name. Unlike `with_extension`, the
+/// dots already in a script name are kept.
+pub fn artifact_file(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(extension);
+    path.with_file_name(file_name)
+}
+
+fn write_output(kind: &str, source: &Path, path: &PathBuf, buf: &[u8]) {
     let mut f = fs::File::create(path).expect("Error occurs on create output file");
     f.write_all(&buf)
         .expect("Error occurs on writing output file");
+    emit_artifact(kind, source, path);
+}
+
+#[test]
+fn test_parse_frames() {
+    // As dumped by `Interpreter::get_internal_state` of move-vm-runtime, frames are printed
+    // with `FunctionRef::pretty_string`: `0x<short address>.<module>.<function>`.
+    let message = "Call stack:\n \
+                   frame #0: 0x00000000.<SELF>.main [pc = 4]\n\
+                   *frame #1: 0x00000000.Coin.pay [pc = 2]:\n\
+                   0> CopyLoc(0)\n\
+                   1> LdU64(10)\n\
+                   2* Abort\n\
+                   Locals:\n\
+                   [0]: 1\n\
+                   \n\
+                   Operand Stack:\n";
+    assert_eq!(
+        parse_frames(message),
+        vec![
+            Frame {
+                address: "00000000".to_string(),
+                module: "Coin".to_string(),
+                function: "pay".to_string(),
+                pc: 2
+            },
+            Frame {
+                address: "00000000".to_string(),
+                module: "<SELF>".to_string(),
+                function: "main".to_string(),
+                pc: 4
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_manifest() {
+    let home = std::env::temp_dir().join(format!("move-manifest-{}", std::process::id()));
+    let mut cfg = Config::new("manifest".to_string(), home.clone());
+    cfg.compile.skip_stdlib = true;
+    cfg.initial();
+    let module = cfg.module_dir().join("M.mvir");
+    let script = cfg.script_dir().join("main.mvir");
+    fs::write(
+        &module,
+        "module M {\n    resource T { v: u64 }\n    public f(t: &mut Self.T, v: vector<u64>) {\n        return;\n    }\n}",
+    )
+    .unwrap();
+    fs::write(
+        &script,
+        "import {{sender}}.M;\nmain(n: u64, to: address, data: vector<u8>) {\n    return;\n}",
+    )
+    .unwrap();
+
+    let mut runner = MoveRunner::new(cfg.clone()).unwrap();
+    let compiled = runner.complie_module(&module).unwrap();
+    runner.complie_script(&script).unwrap();
+    let manifest = runner.manifest(&home);
+    fs::remove_dir_all(&home).unwrap();
+
+    let m = compiled.as_inner();
+    let handle = m.function_handle_at(m.function_defs()[0].function);
+    let parameters: Vec<String> = m
+        .signature_at(handle.parameters)
+        .0
+        .iter()
+        .map(|token| type_name(m, token))
+        .collect();
+    assert_eq!(parameters, vec!["&mut T", "vector<u64>"]);
+
+    assert_eq!(manifest.modules.len(), 1);
+    assert_eq!(manifest.modules[0].name, "M");
+    assert_eq!(manifest.modules[0].address, format!("0x{:x}", cfg.address()));
+    assert_eq!(manifest.modules[0].source, PathBuf::from("src/modules/M.mvir"));
+    assert_eq!(manifest.modules[0].path, Some(PathBuf::from("target/modules/M.mv")));
+    assert_eq!(manifest.modules[0].hash, lockfile::hash(&module_bytes(m)));
+    assert!(manifest.modules[0].dependencies.is_empty());
+    assert_eq!(manifest.scripts.len(), 1);
+    assert_eq!(manifest.scripts[0].source, PathBuf::from("src/scripts/main.mvir"));
+    assert_eq!(manifest.scripts[0].type_parameters, 0);
+    assert_eq!(manifest.scripts[0].parameters, vec!["u64", "address", "vector<u8>"]);
+}
+
+#[test]
+fn test_locate() {
+    let home = std::env::temp_dir().join(format!("move-locate-{}", std::process::id()));
+    let mut cfg = Config::new("locate".to_string(), home.clone());
+    cfg.compile.skip_stdlib = true;
+    cfg.initial();
+    let module = cfg.module_dir().join("M.mvir");
+    let first = cfg.script_dir().join("first.mvir");
+    let second = cfg.script_dir().join("second.mvir");
+    fs::write(&module, "module M {\n    public f() {\n        return;\n    }\n}").unwrap();
+    fs::write(&first, "main() {\n    return;\n}").unwrap();
+    fs::write(&second, "import {{sender}}.M;\nmain() {\n    M.f();\n    return;\n}").unwrap();
+
+    let mut runner = MoveRunner::new(cfg.clone()).unwrap();
+    runner.complie_module(&module).unwrap();
+    runner.complie_script(&first).unwrap();
+    runner.complie_script(&second).unwrap();
+    fs::remove_dir_all(&home).unwrap();
+
+    let address = format!("{:x}", cfg.address());
+    let message = format!(
+        "frame #0: 0x{0}.<SELF>.main [pc = 0]\nframe #1: 0x{0}.M.f [pc = 0]\n",
+        &address[..8]
+    );
+    let frames = parse_frames(&message);
+    let located: Vec<PathBuf> = frames
+        .iter()
+        .filter_map(|frame| runner.locate(&second, frame, Some(frame.pc)))
+        .map(|(path, _)| path)
+        .collect();
+    assert_eq!(located, vec![module.clone(), second.clone()]);
+
+    let other = Frame {
+        address: "ffffffff".to_string(),
+        ..parse_frames(&message).remove(0)
+    };
+    assert!(runner.locate(&second, &other, None).is_none());
+}
+
+#[test]
+fn test_artifact_file() {
+    assert_eq!(
+        artifact_file(Path::new("target/scripts/a.b"), "mv"),
+        PathBuf::from("target/scripts/a.b.mv")
+    );
+    assert_eq!(
+        artifact_file(Path::new("target/modules/Coin"), "mvsm"),
+        PathBuf::from("target/modules/Coin.mvsm")
+    );
+}
+
+#[test]
+fn test_span_from_message() {
+    assert_eq!(span_from_message("Invalid token at [12, 15)"), Some(12..15));
+    assert_eq!(span_from_message("Unbound module [M]"), None);
+}
+
+#[test]
+fn test_parse_verifier_location() {
+    assert_eq!(
+        parse_verifier_location("At offset 4 at index 1 while indexing function definition"),
+        (Some(1), Some(4))
+    );
+    assert_eq!(
+        parse_verifier_location("at index 2 while indexing struct definition"),
+        (None, None)
+    );
 }